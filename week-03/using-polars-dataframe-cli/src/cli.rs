@@ -1,4 +1,8 @@
-use clap::Parser;
+use clap::{Args, Parser};
+use polars::prelude::{CsvEncoding, DataType, JoinType, ParquetCompression};
+use using_polars_dataframe_cli::{
+    parse_compression, parse_dtype, parse_join_type, Aggregation, CsvOptions, Format, SortKey,
+};
 
 const CSV_FILE: &str = "data/global-life-expt-2022.csv";
 
@@ -13,6 +17,109 @@ const CSV_FILE: &str = "data/global-life-expt-2022.csv";
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    #[command(flatten)]
+    pub input: InputArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+//where the resulting dataframe is written instead of printed
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Write the whole result to this file instead of printing it, not for schema and shape
+    #[arg(long, global = true)]
+    pub output: Option<String>,
+    /// csv, tsv, parquet, json, ndjson or ipc, told by the output extension when not set
+    #[arg(long, global = true, value_parser = Format::parse)]
+    pub output_format: Option<Format>,
+    /// Parquet compression: uncompressed, snappy, gzip, lz4, zstd or brotli
+    #[arg(long, global = true, default_value = "zstd", value_parser = parse_compression)]
+    pub compression: ParquetCompression,
+}
+
+//how the input files are read, accepted by every subcommand
+#[derive(Args)]
+pub struct InputArgs {
+    /// csv, tsv, parquet, json, ndjson or ipc, told by the file extension when not set
+    #[arg(long, global = true, value_parser = Format::parse)]
+    pub format: Option<Format>,
+    /// Column delimiter, a tab for tsv files and a comma otherwise
+    #[arg(long, global = true)]
+    delimiter: Option<char>,
+    /// The first row is data, columns are named column_1, column_2...
+    #[arg(long, global = true)]
+    no_header: bool,
+    #[arg(long, global = true, default_value = "\"")]
+    quote_char: char,
+    /// Read quotes as ordinary characters
+    #[arg(long, global = true)]
+    no_quoting: bool,
+    /// Values read as null, e.g. --null-values "NA,n/a"
+    #[arg(long, global = true, value_delimiter = ',')]
+    null_values: Vec<String>,
+    /// Column types instead of the inferred ones, e.g. --dtypes "2020:f64,Country Code:str"
+    #[arg(long, global = true, value_delimiter = ',', value_parser = parse_column_dtype)]
+    dtypes: Vec<(String, DataType)>,
+    /// Rows skipped at the top of the file, before the header
+    #[arg(long, global = true, default_value = "0")]
+    skip_rows: usize,
+    /// utf8, or lossy-utf8 to replace invalid characters instead of failing
+    #[arg(long, global = true, default_value = "utf8", value_parser = parse_encoding)]
+    encoding: CsvEncoding,
+    /// Rows read to infer the column types, 0 reads the whole file
+    #[arg(long, global = true, default_value = "100")]
+    infer_schema_length: usize,
+}
+
+impl InputArgs {
+    pub fn options(&self) -> Result<CsvOptions, String> {
+        let byte = |c: char, name: &str| {
+            if c.is_ascii() {
+                Ok(c as u8)
+            } else {
+                Err(format!("the {} must be an ASCII character", name))
+            }
+        };
+        Ok(CsvOptions {
+            delimiter: self.delimiter.map(|c| byte(c, "delimiter")).transpose()?,
+            has_header: !self.no_header,
+            quote_char: if self.no_quoting {
+                None
+            } else {
+                Some(byte(self.quote_char, "quote char")?)
+            },
+            null_values: self.null_values.clone(),
+            dtypes: self.dtypes.clone(),
+            skip_rows: self.skip_rows,
+            encoding: self.encoding,
+            infer_schema_length: (self.infer_schema_length > 0).then_some(self.infer_schema_length),
+        })
+    }
+}
+
+fn parse_column_dtype(text: &str) -> Result<(String, DataType), String> {
+    let (column, dtype) = text
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected column:type, got {:?}", text))?;
+    Ok((column.to_string(), parse_dtype(dtype)?))
+}
+
+fn parse_table(text: &str) -> Result<(String, String), String> {
+    let (name, path) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=path, got {:?}", text))?;
+    Ok((name.to_string(), path.to_string()))
+}
+
+fn parse_encoding(text: &str) -> Result<CsvEncoding, String> {
+    match text {
+        "utf8" => Ok(CsvEncoding::Utf8),
+        "lossy-utf8" => Ok(CsvEncoding::LossyUtf8),
+        _ => Err(format!(
+            "unknown encoding {}, expected utf8 or lossy-utf8",
+            text
+        )),
+    }
 }
 
 #[derive(Parser)]
@@ -26,6 +133,9 @@ pub enum Commands {
     Describe {
         #[arg(long, default_value = CSV_FILE)]
         path: String,
+        /// Columns to describe, all of them when not set
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
     },
     Schema {
         #[arg(long, default_value = CSV_FILE)]
@@ -35,14 +145,110 @@ pub enum Commands {
         #[arg(long, default_value = CSV_FILE)]
         path: String,
     },
+    /// Print the rows matching a predicate, e.g. '"Country Name" == "France"' or '2020 > 80 and 2019 < 75'
+    Filter {
+        predicate: String,
+        #[arg(long, default_value = CSV_FILE)]
+        path: String,
+        #[arg(long, default_value = "10")]
+        rows: usize,
+    },
+    /// Sort by one or more columns, e.g. --by "2020:desc,Country Name"
     Sort {
         #[arg(long, default_value = CSV_FILE)]
         path: String,
-        #[arg(long, default_value = "2020")]
-        year: String,
+        /// Sort columns, each followed by :asc (the default) or :desc
+        #[arg(long, required = true, value_delimiter = ',', value_parser = SortKey::parse)]
+        by: Vec<SortKey>,
+        /// Put the nulls before the other values instead of after them
+        #[arg(long)]
+        nulls_first: bool,
+        /// Drop the rows with a null in one of the sort columns
+        #[arg(long)]
+        drop_nulls: bool,
+        /// Columns to print, all of them when not set
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
         #[arg(long, default_value = "10")]
         rows: usize,
-        #[arg(long, default_value = "true")]
-        order: bool,
     },
+    /// Aggregate groups of rows, e.g. --by "Country Code" --agg "mean(2020),max(2021),count()"
+    Groupby {
+        #[arg(long, default_value = CSV_FILE)]
+        path: String,
+        /// Columns identifying a group
+        #[arg(long, required = true, value_delimiter = ',')]
+        by: Vec<String>,
+        /// Aggregations: count() or count, n_unique, sum, mean, median, min, max, std, var,
+        /// first or last of a column
+        #[arg(long, required = true, value_delimiter = ',', value_parser = Aggregation::parse)]
+        agg: Vec<Aggregation>,
+        #[arg(long, default_value = "10")]
+        rows: usize,
+    },
+    /// Join two files on key columns, e.g. --right regions.csv --on "Country Code" --how left
+    Join {
+        #[arg(long, default_value = CSV_FILE)]
+        path: String,
+        #[arg(long)]
+        right: String,
+        /// Key columns, named the same in both files
+        #[arg(long, value_delimiter = ',', required_unless_present = "left_on")]
+        on: Vec<String>,
+        /// Key columns of the left file, when named differently from the right ones
+        #[arg(
+            long,
+            value_delimiter = ',',
+            conflicts_with = "on",
+            requires = "right_on"
+        )]
+        left_on: Vec<String>,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            conflicts_with = "on",
+            requires = "left_on"
+        )]
+        right_on: Vec<String>,
+        /// inner, left, outer, anti or semi
+        #[arg(long, default_value = "inner", value_parser = parse_join_type)]
+        how: JoinType,
+        /// Added to the right columns named like a left column
+        #[arg(long, default_value = "_right")]
+        suffix: String,
+        #[arg(long, default_value = "10")]
+        rows: usize,
+    },
+    /// Run a SQL query, the --path file is table t, e.g. 'SELECT "Country Name" FROM t WHERE "2020" > 84'
+    Query {
+        sql: String,
+        #[arg(long, default_value = CSV_FILE)]
+        path: String,
+        /// More tables, e.g. --table regions=regions.csv
+        #[arg(long = "table", value_parser = parse_table)]
+        tables: Vec<(String, String)>,
+        #[arg(long, default_value = "10")]
+        rows: usize,
+    },
+    /// Convert a file to another format, e.g. --path data.csv --output data.parquet
+    Convert {
+        #[arg(long, default_value = CSV_FILE)]
+        path: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_puts_nulls_last_by_default() {
+        let sort = |args: &[&str]| match Cli::parse_from(args).command {
+            Some(Commands::Sort { nulls_first, .. }) => nulls_first,
+            _ => panic!("expected the sort command"),
+        };
+
+        assert!(!sort(&["cli", "sort", "--by", "2020:desc"]));
+        assert!(sort(&["cli", "sort", "--by", "2020:desc", "--nulls-first"]));
+    }
 }
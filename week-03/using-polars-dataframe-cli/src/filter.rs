@@ -0,0 +1,322 @@
+// predicates of the filter subcommand, compiled into polars expressions
+//
+//     "Country Name" == "France"
+//     2020 > 80 and 2019 < 75
+//     not ("Country Code" == "FRA" or 2020 is null)
+//
+// the left side of a comparison is a column, bare or in double quotes, so year columns can be
+// written as numbers. The right side is a value: a number, a string in double quotes, true,
+// false or null. A bare word on the right side that is not a number is another column,
+// so a number there is always a value.
+// Comparisons are ==, !=, <, <=, >, >=, `is null` and `is not null`, combined with
+// and, or, not and parentheses. `== null` and `!= null` are read as `is null` and
+// `is not null`, other comparisons with null are refused. Keywords are case insensitive.
+use polars::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    //bare word: column name, number or keyword
+    Word(String),
+    //text in double quotes
+    Quoted(String),
+    Op(String),
+    LParen,
+    RParen,
+}
+
+//compile a predicate into an expression for LazyFrame::filter
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!(
+            "unexpected {} after the predicate",
+            describe(token)
+        )),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' {
+                Token::LParen
+            } else {
+                Token::RParen
+            });
+        } else if c == '"' {
+            chars.next();
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => quoted.push(escaped),
+                        None => return Err("unterminated string".to_string()),
+                    },
+                    Some(c) => quoted.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Quoted(quoted));
+        } else if "=!<>".contains(c) {
+            chars.next();
+            let mut op = c.to_string();
+            if chars.peek() == Some(&'=') {
+                op.push('=');
+                chars.next();
+            }
+            if op == "=" || op == "!" {
+                return Err(format!("unknown operator {}, use == or !=", op));
+            }
+            tokens.push(Token::Op(op));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || "()\"=!<>".contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(w) => format!("'{}'", w),
+        Token::Quoted(q) => format!("\"{}\"", q),
+        Token::Op(op) => format!("'{}'", op),
+        Token::LParen => "'('".to_string(),
+        Token::RParen => "')'".to_string(),
+    }
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = is_keyword(self.peek(), keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = expr.or(self.and()?);
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = expr.and(self.not()?);
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(self.not()?.not());
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.or()?;
+            return match self.next() {
+                Some(Token::RParen) => Ok(expr),
+                _ => Err("missing ')'".to_string()),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let column = match self.next() {
+            Some(Token::Word(w)) | Some(Token::Quoted(w)) => col(&w),
+            Some(token) => return Err(format!("expected a column, got {}", describe(&token))),
+            None => return Err("expected a column, got the end of the predicate".to_string()),
+        };
+
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("null") {
+                return Err("expected null after is".to_string());
+            }
+            return Ok(if negated {
+                column.is_not_null()
+            } else {
+                column.is_null()
+            });
+        }
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(token) => {
+                return Err(format!(
+                    "expected a comparison after the column, got {}",
+                    describe(&token)
+                ))
+            }
+            None => return Err("expected a comparison after the column".to_string()),
+        };
+        //a comparison with a null value is null itself, so it would match no row
+        if self.keyword("null") {
+            return match op.as_str() {
+                "==" => Ok(column.is_null()),
+                "!=" => Ok(column.is_not_null()),
+                _ => Err(format!(
+                    "{} null matches no row, use is null or is not null",
+                    op
+                )),
+            };
+        }
+        let value = self.value()?;
+
+        Ok(match op.as_str() {
+            "==" => column.eq(value),
+            "!=" => column.neq(value),
+            "<" => column.lt(value),
+            "<=" => column.lt_eq(value),
+            ">" => column.gt(value),
+            ">=" => column.gt_eq(value),
+            _ => unreachable!("operators are checked by tokenize"),
+        })
+    }
+
+    fn value(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Quoted(q)) => Ok(lit(q)),
+            Some(Token::Word(w)) => {
+                if let Ok(n) = w.parse::<i64>() {
+                    Ok(lit(n))
+                } else if let Ok(n) = w.parse::<f64>() {
+                    Ok(lit(n))
+                } else if w.eq_ignore_ascii_case("true") {
+                    Ok(lit(true))
+                } else if w.eq_ignore_ascii_case("false") {
+                    Ok(lit(false))
+                } else {
+                    Ok(col(&w))
+                }
+            }
+            Some(token) => Err(format!("expected a value, got {}", describe(&token))),
+            None => Err("expected a value, got the end of the predicate".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn countries() -> DataFrame {
+        df!(
+            "Country Name" => ["France", "Peru", "Chad", "Say \"hi\""],
+            "2019" => [Some(82.7), Some(76.7), None, Some(70.0)],
+            "2020" => [Some(82.2), Some(76.5), Some(52.8), None],
+            "floor" => [80.0, 80.0, 50.0, 50.0],
+        )
+        .unwrap()
+    }
+
+    //names of the countries matching the predicate
+    fn matching(predicate: &str) -> Vec<String> {
+        let expr = parse(predicate).unwrap_or_else(|e| panic!("{}: {}", predicate, e));
+        let df = countries().lazy().filter(expr).collect().unwrap();
+        df.column("Country Name")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|name| name.unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn quoted_and_numeric_column_names() {
+        assert_eq!(matching(r#""Country Name" == "Peru""#), ["Peru"]);
+        assert_eq!(matching("2020 > 80"), ["France"]);
+        assert_eq!(matching("2020 > floor"), ["France", "Chad"]);
+        assert_eq!(matching("2020 < 2019"), ["France", "Peru", "Chad"]);
+        assert_eq!(matching("2020 >= 76.5"), ["France", "Peru"]);
+    }
+
+    #[test]
+    fn escaped_quotes_in_strings() {
+        assert_eq!(
+            matching(r#""Country Name" == "Say \"hi\"""#),
+            ["Say \"hi\""]
+        );
+        assert_eq!(
+            parse(r#""Country Name" == "Peru"#),
+            Err("unterminated string".to_string())
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(matching("2020 > 80 or 2020 < 60 and 2019 > 0"), ["France"]);
+        assert_eq!(
+            matching("(2020 > 80 or 2020 < 60) and 2019 is null"),
+            ["Chad"]
+        );
+        assert_eq!(matching("not 2020 > 80 AND 2020 > 70"), ["Peru"]);
+        assert_eq!(matching("not (2020 > 80 or 2020 < 60)"), ["Peru"]);
+    }
+
+    #[test]
+    fn null_tests() {
+        assert_eq!(matching("2019 is null"), ["Chad"]);
+        assert_eq!(
+            matching("2020 IS NOT NULL and 2019 is not null"),
+            ["France", "Peru"]
+        );
+        assert_eq!(matching("2020 == null"), [r#"Say "hi""#]);
+        assert_eq!(matching("2019 != null"), ["France", "Peru", r#"Say "hi""#]);
+        assert!(parse("2020 > null").is_err());
+        assert!(parse("2020 is 3").is_err());
+    }
+
+    #[test]
+    fn malformed_predicates() {
+        assert!(parse("2020 = 80").is_err());
+        assert!(parse("(2020 > 80").is_err());
+        assert!(parse("2020 > 80)").is_err());
+        assert!(parse("2020 >").is_err());
+        assert!(parse("2020 80").is_err());
+        assert!(parse("> 80").is_err());
+    }
+}
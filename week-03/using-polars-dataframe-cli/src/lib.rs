@@ -1,10 +1,523 @@
 // utilities for working with polars dataframes
 //
+pub mod filter;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
 use polars::prelude::*;
+use polars::sql::SQLContext;
+
+//how a csv file is read
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    //None is a tab for tsv files and a comma otherwise
+    pub delimiter: Option<u8>,
+    pub has_header: bool,
+    //None reads quotes as ordinary characters
+    pub quote_char: Option<u8>,
+    //values read as null in every column
+    pub null_values: Vec<String>,
+    //column types used instead of the inferred ones
+    pub dtypes: Vec<(String, DataType)>,
+    pub skip_rows: usize,
+    pub encoding: CsvEncoding,
+    //rows read to infer the column types, None reads the whole file
+    pub infer_schema_length: Option<usize>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: None,
+            has_header: true,
+            quote_char: Some(b'"'),
+            null_values: Vec::new(),
+            dtypes: Vec::new(),
+            skip_rows: 0,
+            encoding: CsvEncoding::Utf8,
+            infer_schema_length: Some(100),
+        }
+    }
+}
+
+impl CsvOptions {
+    //the delimiter of a file in the given format
+    fn separator(&self, format: Format) -> u8 {
+        match (self.delimiter, format) {
+            (Some(delimiter), _) => delimiter,
+            (None, Format::Tsv) => b'\t',
+            (None, _) => b',',
+        }
+    }
+}
+
+//file formats read by the cli
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Tsv,
+    Parquet,
+    Json,
+    NdJson,
+    Ipc,
+}
+
+impl Format {
+    //csv, tsv, parquet, json, ndjson (or jsonl) and ipc (or arrow, feather)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "parquet" | "pq" => Ok(Format::Parquet),
+            "json" => Ok(Format::Json),
+            "ndjson" | "jsonl" => Ok(Format::NdJson),
+            "ipc" | "arrow" | "feather" => Ok(Format::Ipc),
+            _ => Err(format!(
+                "unknown format {}, expected csv, tsv, parquet, json, ndjson or ipc",
+                text
+            )),
+        }
+    }
+
+    //format named by the file extension
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| format!("no extension to tell the format of {}, use --format", path))?;
+        Format::parse(extension)
+    }
+}
+
+//scan a file lazily in the given format, or the one of its extension when None, so only
+//the columns and rows a query needs are read. json and ipc files cannot be scanned and are
+//read whole. csv options only apply to csv and tsv files
+pub fn scan(path: &str, format: Option<Format>, options: &CsvOptions) -> PolarsResult<LazyFrame> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::from_path(path).map_err(|e| polars_err!(InvalidOperation: "{}", e))?,
+    };
+
+    match format {
+        Format::Csv | Format::Tsv => scan_csv(path, format, options),
+        Format::Parquet => LazyFrame::scan_parquet(path, ScanArgsParquet::default()),
+        Format::Json => {
+            let df = JsonReader::new(File::open(path)?).finish()?;
+            in_file_order(df.lazy(), path, format)
+        }
+        Format::NdJson => in_file_order(LazyJsonLineReader::new(path).finish()?, path, format),
+        //the ipc scan of polars 0.37 panics when collected with the streaming engine, the
+        //file is memory mapped instead so only the parts a query reads are loaded
+        Format::Ipc => IpcReader::new(File::open(path)?)
+            .memory_mapped(true)
+            .finish()
+            .map(DataFrame::lazy),
+    }
+}
+
+//put the columns of a json file in the order their keys first appear in its records.
+//polars reads the records with more than 32 keys into hash maps and shuffles the columns
+fn in_file_order(lf: LazyFrame, path: &str, format: Format) -> PolarsResult<LazyFrame> {
+    let schema = lf.schema()?;
+    let mut keys = Vec::new();
+    let mut seen = HashSet::new();
+    for record in json_records(path, format)? {
+        for key in record
+            .as_object()
+            .into_iter()
+            .flat_map(|record| record.keys())
+        {
+            if schema.contains(key) && seen.insert(key.clone()) {
+                keys.push(key.clone());
+            }
+        }
+    }
+    //columns no record was read for stay after the others
+    let rest = schema
+        .iter_names()
+        .filter(|name| !seen.contains(name.as_str()));
+    let columns = keys.iter().map(|key| col(key));
+    Ok(lf.select(
+        columns
+            .chain(rest.map(|name| col(name)))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+//records of a json file, only the first 100 of an ndjson file as polars infers its schema
+//from them
+fn json_records(path: &str, format: Format) -> PolarsResult<Vec<serde_json::Value>> {
+    let file = BufReader::new(File::open(path)?);
+    if format == Format::NdJson {
+        let mut records = Vec::new();
+        for line in file.lines() {
+            let line = line?;
+            if records.len() == 100 {
+                break;
+            }
+            if !line.trim().is_empty() {
+                records.push(
+                    serde_json::from_str(&line).map_err(|e| polars_err!(ComputeError: "{}", e))?,
+                );
+            }
+        }
+        return Ok(records);
+    }
+
+    match serde_json::from_reader(file).map_err(|e| polars_err!(ComputeError: "{}", e))? {
+        serde_json::Value::Array(records) => Ok(records),
+        record => Ok(vec![record]),
+    }
+}
+
+fn scan_csv(path: &str, format: Format, options: &CsvOptions) -> PolarsResult<LazyFrame> {
+    let dtypes = (!options.dtypes.is_empty()).then(|| {
+        Schema::from_iter(
+            options
+                .dtypes
+                .iter()
+                .map(|(name, dtype)| Field::new(name, dtype.clone())),
+        )
+    });
+    let null_values = (!options.null_values.is_empty())
+        .then(|| NullValues::AllColumns(options.null_values.clone()));
+
+    LazyCsvReader::new(path)
+        .with_separator(options.separator(format))
+        .has_header(options.has_header)
+        .with_quote_char(options.quote_char)
+        .with_null_values(null_values)
+        .with_dtype_overwrite(dtypes.as_ref())
+        .with_skip_rows(options.skip_rows)
+        .with_encoding(options.encoding)
+        .with_infer_schema_length(options.infer_schema_length)
+        .finish()
+}
+
+//run a query with the streaming engine, which works on batches of rows and so on data
+//larger than memory, falling back to the in-memory engine for what it does not support
+pub fn collect(lf: LazyFrame) -> PolarsResult<DataFrame> {
+    lf.with_streaming(true).collect()
+}
+
+//rows and columns of a scanned file, streaming a single column of it
+pub fn shape(lf: LazyFrame) -> PolarsResult<(usize, usize)> {
+    let schema = lf.schema()?;
+    let first = match schema.iter_names().next() {
+        Some(name) => name.to_string(),
+        None => return Ok((0, 0)),
+    };
+    //one bit per row is kept, polars' own row count is not streamed and ignores skipped rows
+    let rows = collect(lf.select([col(&first).is_null()]))?;
+    Ok((rows.height(), schema.len()))
+}
+
+//write a dataframe in the given format, or the one of the file extension when None.
+//parquet files are compressed with `compression`
+pub fn write(
+    df: &mut DataFrame,
+    path: &str,
+    format: Option<Format>,
+    compression: ParquetCompression,
+) -> PolarsResult<()> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::from_path(path).map_err(|e| polars_err!(InvalidOperation: "{}", e))?,
+    };
+    let file = File::create(path)?;
+
+    match format {
+        Format::Csv => CsvWriter::new(file).finish(df),
+        Format::Tsv => CsvWriter::new(file).with_separator(b'\t').finish(df),
+        Format::Parquet => ParquetWriter::new(file)
+            .with_compression(compression)
+            .finish(df)
+            .map(|_| ()),
+        Format::Json => JsonWriter::new(file)
+            .with_json_format(JsonFormat::Json)
+            .finish(df),
+        Format::NdJson => JsonWriter::new(file)
+            .with_json_format(JsonFormat::JsonLines)
+            .finish(df),
+        Format::Ipc => IpcWriter::new(file).finish(df),
+    }
+}
+
+//stream the result of a query to a file in the given format, or the one of the file
+//extension when None, without holding it in memory. json files, and queries the streaming
+//engine cannot run whole, are collected and written with write
+pub fn sink(
+    lf: LazyFrame,
+    path: &str,
+    format: Option<Format>,
+    compression: ParquetCompression,
+) -> PolarsResult<()> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::from_path(path).map_err(|e| polars_err!(InvalidOperation: "{}", e))?,
+    };
+    let csv = |separator| CsvWriterOptions {
+        serialize_options: SerializeOptions {
+            separator,
+            ..SerializeOptions::default()
+        },
+        ..CsvWriterOptions::default()
+    };
 
-//read in a csv file
-pub fn read_csv(path: &str) -> DataFrame {
-    CsvReader::from_path(path).unwrap().finish().unwrap()
+    //the sinks check that the query streams before creating the file, and fail with a
+    //compute error or an invalid operation when it does not
+    let sunk = match format {
+        Format::Csv => lf.clone().sink_csv(path.into(), csv(b',')),
+        Format::Tsv => lf.clone().sink_csv(path.into(), csv(b'\t')),
+        Format::Parquet => lf.clone().sink_parquet(
+            path.into(),
+            ParquetWriteOptions {
+                compression,
+                statistics: false,
+                row_group_size: None,
+                data_pagesize_limit: None,
+                maintain_order: true,
+            },
+        ),
+        Format::NdJson => lf.clone().sink_json(
+            path.into(),
+            JsonWriterOptions {
+                maintain_order: true,
+            },
+        ),
+        Format::Ipc => lf.clone().sink_ipc(
+            path.into(),
+            IpcWriterOptions {
+                compression: None,
+                maintain_order: true,
+            },
+        ),
+        Format::Json => Err(polars_err!(ComputeError: "json files are not streamed")),
+    };
+    match sunk {
+        Err(PolarsError::ComputeError(_) | PolarsError::InvalidOperation(_)) => {
+            write(&mut lf.collect()?, path, Some(format), compression)
+        }
+        result => result,
+    }
+}
+
+//uncompressed, snappy, gzip, lz4, zstd or brotli
+pub fn parse_compression(text: &str) -> Result<ParquetCompression, String> {
+    match text.to_lowercase().as_str() {
+        "uncompressed" | "none" => Ok(ParquetCompression::Uncompressed),
+        "snappy" => Ok(ParquetCompression::Snappy),
+        "gzip" => Ok(ParquetCompression::Gzip(None)),
+        "lz4" => Ok(ParquetCompression::Lz4Raw),
+        "zstd" => Ok(ParquetCompression::Zstd(None)),
+        "brotli" => Ok(ParquetCompression::Brotli(None)),
+        _ => Err(format!(
+            "unknown compression {}, expected uncompressed, snappy, gzip, lz4, zstd or brotli",
+            text
+        )),
+    }
+}
+
+//a column type given on the command line: str, bool, i32, i64, u32, u64, f32, f64 or date
+pub fn parse_dtype(text: &str) -> Result<DataType, String> {
+    match text.to_lowercase().as_str() {
+        "str" | "string" => Ok(DataType::String),
+        "bool" | "boolean" => Ok(DataType::Boolean),
+        "i32" | "int32" => Ok(DataType::Int32),
+        "i64" | "int64" => Ok(DataType::Int64),
+        "u32" | "uint32" => Ok(DataType::UInt32),
+        "u64" | "uint64" => Ok(DataType::UInt64),
+        "f32" | "float32" => Ok(DataType::Float32),
+        "f64" | "float64" => Ok(DataType::Float64),
+        "date" => Ok(DataType::Date),
+        _ => Err(format!(
+            "unknown type {}, expected str, bool, i32, i64, u32, u64, f32, f64 or date",
+            text
+        )),
+    }
+}
+
+//keep the rows matching a predicate, see filter::parse for the syntax
+pub fn filter(lf: LazyFrame, predicate: &str) -> Result<LazyFrame, String> {
+    Ok(lf.filter(filter::parse(predicate)?))
+}
+
+//a sort column, written `name`, `name:asc` or `name:desc`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl SortKey {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (column, descending) = match text.rsplit_once(':') {
+            Some((column, "asc")) => (column, false),
+            Some((column, "desc")) => (column, true),
+            _ => (text, false),
+        };
+        if column.is_empty() {
+            return Err(format!("missing column name in {:?}", text));
+        }
+        Ok(SortKey {
+            column: column.to_string(),
+            descending,
+        })
+    }
+}
+
+//sort by several columns, each ascending or descending, ties keep the file order.
+//`columns` keeps only those columns in the result, all of them when empty, and
+//`drop_nulls` removes the rows with a null in one of the sort columns
+pub fn sort(
+    mut lf: LazyFrame,
+    keys: &[SortKey],
+    nulls_last: bool,
+    columns: &[String],
+    drop_nulls: bool,
+) -> LazyFrame {
+    let by: Vec<Expr> = keys.iter().map(|k| col(&k.column)).collect();
+    let descending: Vec<bool> = keys.iter().map(|k| k.descending).collect();
+
+    if drop_nulls {
+        lf = lf.drop_nulls(Some(by.clone()));
+    }
+    lf = lf.sort_by_exprs(by, descending, nulls_last, true);
+    if !columns.is_empty() {
+        lf = lf.select(columns.iter().map(|c| col(c)).collect::<Vec<_>>());
+    }
+    lf
+}
+
+//an aggregation of the groupby subcommand: `count()` for the number of rows of the group,
+//or a function of a column among count, n_unique, sum, mean, median, min, max, std, var,
+//first and last, e.g. `mean(2020)` or `max("Country Name")`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregation {
+    pub function: String,
+    pub column: Option<String>,
+}
+
+const AGGREGATIONS: [&str; 11] = [
+    "count", "n_unique", "sum", "mean", "median", "min", "max", "std", "var", "first", "last",
+];
+
+impl Aggregation {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (function, column) = text
+            .trim()
+            .strip_suffix(')')
+            .and_then(|t| t.split_once('('))
+            .ok_or_else(|| format!("expected function(column), got {:?}", text))?;
+        let function = function.trim().to_lowercase();
+        let column = column.trim();
+        let column = column
+            .strip_prefix('"')
+            .and_then(|c| c.strip_suffix('"'))
+            .unwrap_or(column);
+
+        if !AGGREGATIONS.contains(&function.as_str()) {
+            return Err(format!(
+                "unknown aggregation {}, expected one of {}",
+                function,
+                AGGREGATIONS.join(", ")
+            ));
+        }
+        if column.is_empty() && function != "count" {
+            return Err(format!("{}() needs a column", function));
+        }
+        Ok(Aggregation {
+            function,
+            column: (!column.is_empty()).then(|| column.to_string()),
+        })
+    }
+
+    //named after the aggregation, e.g. "mean(2020)"
+    pub fn expr(&self) -> Expr {
+        let column = match &self.column {
+            Some(column) => column,
+            None => return len().alias("count()"),
+        };
+        let c = col(column);
+        let expr = match self.function.as_str() {
+            "count" => c.count(),
+            "n_unique" => c.n_unique(),
+            "sum" => c.sum(),
+            "mean" => c.mean(),
+            "median" => c.median(),
+            "min" => c.min(),
+            "max" => c.max(),
+            "std" => c.std(1),
+            "var" => c.var(1),
+            "first" => c.first(),
+            "last" => c.last(),
+            _ => unreachable!("functions are checked by parse"),
+        };
+        expr.alias(&format!("{}({})", self.function, column))
+    }
+}
+
+//aggregate the rows of each group, groups come in the order of their first row
+pub fn groupby(lf: LazyFrame, by: &[String], aggregations: &[Aggregation]) -> LazyFrame {
+    let by: Vec<Expr> = by.iter().map(|c| col(c)).collect();
+    let aggs: Vec<Expr> = aggregations.iter().map(Aggregation::expr).collect();
+    lf.group_by_stable(by).agg(aggs)
+}
+
+//inner, left, outer, anti or semi
+pub fn parse_join_type(text: &str) -> Result<JoinType, String> {
+    match text.to_lowercase().as_str() {
+        "inner" => Ok(JoinType::Inner),
+        "left" => Ok(JoinType::Left),
+        "outer" => Ok(JoinType::Outer { coalesce: false }),
+        "anti" => Ok(JoinType::Anti),
+        "semi" => Ok(JoinType::Semi),
+        _ => Err(format!(
+            "unknown join type {}, expected inner, left, outer, anti or semi",
+            text
+        )),
+    }
+}
+
+//join two frames on key columns, a right column named like a left one gets `suffix`.
+//anti and semi joins keep the left rows without or with a match, and only the left columns
+pub fn join(
+    left: LazyFrame,
+    right: LazyFrame,
+    left_on: &[String],
+    right_on: &[String],
+    how: JoinType,
+    suffix: &str,
+) -> PolarsResult<LazyFrame> {
+    if left_on.len() != right_on.len() {
+        polars_bail!(
+            ComputeError: "{} left key columns for {} right ones",
+            left_on.len(),
+            right_on.len()
+        );
+    }
+    let keys = |columns: &[String]| columns.iter().map(|c| col(c)).collect::<Vec<_>>();
+
+    Ok(left
+        .join_builder()
+        .with(right)
+        .left_on(keys(left_on))
+        .right_on(keys(right_on))
+        .how(how)
+        .suffix(suffix)
+        .finish())
+}
+
+//plan a sql query over frames registered under their table name
+pub fn query(tables: Vec<(String, LazyFrame)>, sql: &str) -> PolarsResult<LazyFrame> {
+    let mut ctx = SQLContext::new();
+    for (name, lf) in tables {
+        ctx.register(&name, lf);
+    }
+    ctx.execute(sql)
 }
 
 //print "n" rows of a dataframe
@@ -21,3 +534,524 @@ pub fn print_schema(df: &DataFrame) {
 pub fn print_shape(df: &DataFrame) {
     println!("{:?}", df.shape());
 }
+
+//statistics computed by describe, one row each
+const STATISTICS: [&str; 12] = [
+    "count",
+    "null_count",
+    "mean",
+    "std",
+    "min",
+    "25%",
+    "50%",
+    "75%",
+    "max",
+    "unique",
+    "top",
+    "freq",
+];
+
+//per column statistics, like pandas' describe: numeric columns get the mean, std, min,
+//quartiles and max, string columns the distinct count and their most frequent value
+pub fn describe(df: &DataFrame) -> PolarsResult<DataFrame> {
+    let mut columns = vec![Series::new("statistic", STATISTICS.to_vec())];
+    for s in df.get_columns() {
+        let stats = if s.dtype().is_numeric() {
+            numeric_statistics(s)?
+        } else if s.dtype() == &DataType::String {
+            string_statistics(s)?
+        } else {
+            vec![None; STATISTICS.len() - 2]
+        };
+
+        let mut cells = vec![
+            Some((s.len() - s.null_count()).to_string()),
+            Some(s.null_count().to_string()),
+        ];
+        cells.extend(stats);
+        columns.push(Series::new(s.name(), cells));
+    }
+    DataFrame::new(columns)
+}
+
+//mean, std, min, quartiles and max, then nothing for unique, top and freq
+fn numeric_statistics(s: &Series) -> PolarsResult<Vec<Option<String>>> {
+    let values = s.cast(&DataType::Float64)?;
+    let ca = values.f64()?;
+    let quartile = |q| ca.quantile(q, QuantileInterpolOptions::Linear);
+    let stats = [
+        ca.mean(),
+        ca.std(1),
+        ca.min(),
+        quartile(0.25)?,
+        quartile(0.5)?,
+        quartile(0.75)?,
+        ca.max(),
+    ];
+
+    let mut cells: Vec<Option<String>> = stats
+        .iter()
+        .map(|v| v.map(|v| format!("{:.4}", v)))
+        .collect();
+    cells.extend([None, None, None]);
+    Ok(cells)
+}
+
+//nothing for the numeric statistics, then distinct non null values, the most frequent
+//value and its count. Like pandas, a tie goes to the value seen first
+fn string_statistics(s: &Series) -> PolarsResult<Vec<Option<String>>> {
+    //count and first row of every value
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (row, value) in s.str()?.into_iter().enumerate() {
+        if let Some(value) = value {
+            counts.entry(value).or_insert((0, row)).0 += 1;
+        }
+    }
+    let top = counts
+        .iter()
+        .max_by(|a, b| a.1 .0.cmp(&b.1 .0).then(b.1 .1.cmp(&a.1 .1)))
+        .map(|(value, (n, _))| (value.to_string(), n.to_string()));
+
+    let mut cells = vec![None; 7];
+    cells.push(Some(counts.len().to_string()));
+    cells.push(top.as_ref().map(|(value, _)| value.clone()));
+    cells.push(top.map(|(_, n)| n));
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //cell of describe's output for a column and a statistic
+    fn statistic(described: &DataFrame, column: &str, statistic: &str) -> Option<String> {
+        let row = STATISTICS.iter().position(|s| *s == statistic).unwrap();
+        described
+            .column(column)
+            .unwrap()
+            .str()
+            .unwrap()
+            .get(row)
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn describe_statistics() {
+        let df = df!(
+            "age" => [Some(1), Some(2), Some(3), Some(4), None],
+            "name" => [Some("b"), Some("a"), Some("b"), Some("a"), Some("c")],
+            "ok" => [Some(true), Some(false), Some(true), Some(true), None],
+        )
+        .unwrap();
+
+        let described = describe(&df).unwrap();
+
+        let age = |s| statistic(&described, "age", s);
+        assert_eq!(age("count").as_deref(), Some("4"));
+        assert_eq!(age("null_count").as_deref(), Some("1"));
+        assert_eq!(age("mean").as_deref(), Some("2.5000"));
+        assert_eq!(age("std").as_deref(), Some("1.2910"));
+        assert_eq!(age("min").as_deref(), Some("1.0000"));
+        assert_eq!(age("25%").as_deref(), Some("1.7500"));
+        assert_eq!(age("50%").as_deref(), Some("2.5000"));
+        assert_eq!(age("75%").as_deref(), Some("3.2500"));
+        assert_eq!(age("max").as_deref(), Some("4.0000"));
+        assert_eq!(age("unique"), None);
+        assert_eq!(age("top"), None);
+
+        //b and a are both seen twice, b first
+        let name = |s| statistic(&described, "name", s);
+        assert_eq!(name("count").as_deref(), Some("5"));
+        assert_eq!(name("mean"), None);
+        assert_eq!(name("unique").as_deref(), Some("3"));
+        assert_eq!(name("top").as_deref(), Some("b"));
+        assert_eq!(name("freq").as_deref(), Some("2"));
+
+        //neither numeric nor string, only the counts
+        let ok: Vec<Option<String>> = STATISTICS
+            .iter()
+            .map(|s| statistic(&described, "ok", s))
+            .collect();
+        assert_eq!(ok[..2], [Some("4".to_string()), Some("1".to_string())]);
+        assert!(ok[2..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn describe_all_distinct_strings_has_a_single_top() {
+        let df = df!("country" => ["Peru", "Chad", "France"]).unwrap();
+
+        let described = describe(&df).unwrap();
+
+        assert_eq!(
+            statistic(&described, "country", "top").as_deref(),
+            Some("Peru")
+        );
+        assert_eq!(
+            statistic(&described, "country", "freq").as_deref(),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn sort_keys() {
+        let key = SortKey::parse("2020:desc").unwrap();
+        assert_eq!(key.column, "2020");
+        assert!(key.descending);
+
+        let key = SortKey::parse("Country Name:asc").unwrap();
+        assert_eq!(key.column, "Country Name");
+        assert!(!key.descending);
+
+        //a colon not followed by asc or desc is part of the column name
+        let key = SortKey::parse("time:12").unwrap();
+        assert_eq!(key.column, "time:12");
+        assert!(!key.descending);
+
+        assert!(SortKey::parse(":desc").is_err());
+        assert!(SortKey::parse("").is_err());
+    }
+
+    #[test]
+    fn aggregations() {
+        assert_eq!(
+            Aggregation::parse(" MEAN( \"Country Name\" ) ").unwrap(),
+            Aggregation {
+                function: "mean".to_string(),
+                column: Some("Country Name".to_string()),
+            }
+        );
+        assert_eq!(
+            Aggregation::parse("count()").unwrap(),
+            Aggregation {
+                function: "count".to_string(),
+                column: None,
+            }
+        );
+        assert!(Aggregation::parse("mean()").is_err());
+        assert!(Aggregation::parse("mode(2020)").is_err());
+        assert!(Aggregation::parse("mean 2020").is_err());
+    }
+
+    #[test]
+    fn aggregation_exprs_are_named_after_the_aggregation() {
+        let df = df!(
+            "region" => ["north", "south", "north", "north"],
+            "2020" => [Some(1.0), Some(5.0), Some(3.0), None],
+        )
+        .unwrap();
+        let aggregations: Vec<Aggregation> =
+            ["count()", "count(2020)", "mean(2020)", "first(2020)"]
+                .iter()
+                .map(|a| Aggregation::parse(a).unwrap())
+                .collect();
+
+        let out = groupby(df.lazy(), &["region".to_string()], &aggregations)
+            .collect()
+            .unwrap();
+
+        let names: Vec<&str> = out.get_column_names();
+        assert_eq!(
+            names,
+            [
+                "region",
+                "count()",
+                "count(2020)",
+                "mean(2020)",
+                "first(2020)"
+            ]
+        );
+        let expected = df!(
+            "region" => ["north", "south"],
+            "count()" => [3u32, 1],
+            "count(2020)" => [2u32, 1],
+            "mean(2020)" => [2.0, 5.0],
+            "first(2020)" => [1.0, 5.0],
+        )
+        .unwrap();
+        assert!(out.equals(&expected), "{}", out);
+    }
+
+    fn countries() -> LazyFrame {
+        df!(
+            "code" => ["FRA", "PER", "TCD"],
+            "name" => ["France", "Peru", "Chad"],
+        )
+        .unwrap()
+        .lazy()
+    }
+
+    fn populations() -> LazyFrame {
+        df!(
+            "country" => ["PER", "FRA", "USA"],
+            "name" => ["Republic of Peru", "French Republic", "United States"],
+            "population" => [34, 68, 333],
+        )
+        .unwrap()
+        .lazy()
+    }
+
+    fn joined(how: JoinType) -> DataFrame {
+        let on = |c: &str| [c.to_string()];
+        let lf = join(
+            countries(),
+            populations(),
+            &on("code"),
+            &on("country"),
+            how,
+            "_right",
+        );
+        collect(lf.unwrap().sort("code", SortOptions::default())).unwrap()
+    }
+
+    #[test]
+    fn join_on_differently_named_keys_suffixes_colliding_columns() {
+        let inner = joined(JoinType::Inner);
+
+        assert_eq!(
+            inner.get_column_names(),
+            ["code", "name", "name_right", "population"]
+        );
+        let expected = df!(
+            "code" => ["FRA", "PER"],
+            "name" => ["France", "Peru"],
+            "name_right" => ["French Republic", "Republic of Peru"],
+            "population" => [68, 34],
+        )
+        .unwrap();
+        assert!(inner.equals(&expected), "{}", inner);
+    }
+
+    #[test]
+    fn anti_and_semi_joins_keep_left_rows_and_columns() {
+        let anti = joined(JoinType::Anti);
+        let semi = joined(JoinType::Semi);
+
+        assert_eq!(anti.get_column_names(), ["code", "name"]);
+        assert!(anti.equals(&df!("code" => ["TCD"], "name" => ["Chad"]).unwrap()));
+        assert_eq!(semi.get_column_names(), ["code", "name"]);
+        assert!(semi.equals(&df!("code" => ["FRA", "PER"], "name" => ["France", "Peru"]).unwrap()));
+    }
+
+    #[test]
+    fn join_keys_must_pair_up() {
+        let left_on = ["code".to_string(), "name".to_string()];
+        let right_on = ["country".to_string()];
+
+        let result = join(
+            countries(),
+            populations(),
+            &left_on,
+            &right_on,
+            JoinType::Inner,
+            "_right",
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn tsv_files_default_to_tabs() {
+        let path = std::env::temp_dir().join(format!("polars-cli-{}.tsv", std::process::id()));
+        std::fs::write(&path, "name\tscore,max\nada\t1,2\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let tabs = collect(scan(path, None, &CsvOptions::default()).unwrap()).unwrap();
+        let commas = CsvOptions {
+            delimiter: Some(b','),
+            ..CsvOptions::default()
+        };
+        let commas = collect(scan(path, None, &commas).unwrap()).unwrap();
+        let as_csv = scan(path, Some(Format::Csv), &CsvOptions::default()).unwrap();
+        let as_csv = collect(as_csv).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(tabs.get_column_names(), ["name", "score,max"]);
+        assert_eq!(commas.get_column_names(), ["name\tscore", "max"]);
+        assert_eq!(as_csv.get_column_names(), ["name\tscore", "max"]);
+        assert_eq!(Format::parse("TSV"), Ok(Format::Tsv));
+        assert!(Format::parse("txt").is_err());
+    }
+
+    //collect a csv file holding `text` read with `options`
+    fn read_csv(name: &str, text: &str, options: &CsvOptions) -> PolarsResult<DataFrame> {
+        let path =
+            std::env::temp_dir().join(format!("polars-cli-{}-{}.csv", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let df = scan(path.to_str().unwrap(), None, options).and_then(collect);
+        std::fs::remove_file(path).unwrap();
+        df
+    }
+
+    #[test]
+    fn csv_reader_options() {
+        let options = CsvOptions {
+            delimiter: Some(b';'),
+            null_values: vec!["NA".to_string()],
+            dtypes: vec![("score".to_string(), DataType::Float64)],
+            skip_rows: 1,
+            ..CsvOptions::default()
+        };
+        let scores = read_csv(
+            "scores",
+            "exported by hand\nname;score\nada;NA\nbob;7\n",
+            &options,
+        );
+        let scores = scores.unwrap();
+
+        assert_eq!(scores.get_column_names(), ["name", "score"]);
+        let score = scores.column("score").unwrap().f64().unwrap();
+        assert_eq!(score.into_iter().collect::<Vec<_>>(), [None, Some(7.0)]);
+
+        let no_header = CsvOptions {
+            has_header: false,
+            ..CsvOptions::default()
+        };
+        let rows = read_csv("no-header", "1,2\n3,4\n", &no_header).unwrap();
+
+        assert_eq!(rows.get_column_names(), ["column_1", "column_2"]);
+        assert_eq!(rows.height(), 2);
+    }
+
+    #[test]
+    fn csv_quote_char() {
+        let single = CsvOptions {
+            quote_char: Some(b'\''),
+            ..CsvOptions::default()
+        };
+        let quoted = read_csv("single-quotes", "name,n\n'a,b',1\n", &single).unwrap();
+        let unquoted = CsvOptions {
+            quote_char: None,
+            ..CsvOptions::default()
+        };
+        let unquoted = read_csv("no-quotes", "name,n\n\"a,b\",1\n", &unquoted);
+
+        let name = quoted.column("name").unwrap().str().unwrap();
+        assert_eq!(name.get(0), Some("a,b"));
+        assert!(unquoted.is_err());
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_column_order() {
+        //more than 32 columns, not in alphabetical order
+        let names: Vec<_> = (0..40).rev().map(|i| format!("c{}", i)).collect();
+        let columns = names.iter().map(|name| Series::new(name, [1, 2])).collect();
+        let mut df = DataFrame::new(columns).unwrap();
+
+        for extension in ["json", "ndjson"] {
+            let path = std::env::temp_dir().join(format!(
+                "polars-cli-{}-columns.{}",
+                std::process::id(),
+                extension
+            ));
+            let path = path.to_str().unwrap();
+            write(&mut df, path, None, ParquetCompression::Uncompressed).unwrap();
+            let read = collect(scan(path, None, &CsvOptions::default()).unwrap()).unwrap();
+            std::fs::remove_file(path).unwrap();
+
+            assert_eq!(read.get_column_names(), names, "{}", extension);
+        }
+    }
+
+    #[test]
+    fn ipc_round_trip() {
+        let mut df = df!("name" => ["ada", "bob"], "score" => [Some(1.5), None]).unwrap();
+        let path = std::env::temp_dir().join(format!("polars-cli-{}.arrow", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        write(&mut df, path, None, ParquetCompression::Uncompressed).unwrap();
+        let read = collect(scan(path, None, &CsvOptions::default()).unwrap()).unwrap();
+        let shape = shape(scan(path, None, &CsvOptions::default()).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(read.equals_missing(&df), "{}", read);
+        assert_eq!(shape, (2, 2));
+    }
+
+    #[test]
+    fn sort_ties_keep_the_file_order() {
+        let df = df!(
+            "name" => ["d", "c", "b", "a"],
+            "score" => [1, 2, 1, 2],
+        )
+        .unwrap();
+        let keys = [SortKey::parse("score:desc").unwrap()];
+
+        let sorted = collect(sort(df.lazy(), &keys, false, &[], false)).unwrap();
+
+        let expected = df!("name" => ["c", "a", "d", "b"], "score" => [2, 2, 1, 1]).unwrap();
+        assert!(sorted.equals(&expected), "{}", sorted);
+    }
+
+    #[test]
+    fn sort_nulls_placement() {
+        let df = df!("score" => [Some(1), None, Some(3)]).unwrap();
+        let keys = [SortKey::parse("score:desc").unwrap()];
+        let scores = |nulls_last, drop_nulls| {
+            let sorted = sort(df.clone().lazy(), &keys, nulls_last, &[], drop_nulls);
+            let sorted = collect(sorted).unwrap();
+            sorted
+                .column("score")
+                .unwrap()
+                .i32()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(scores(true, false), [Some(3), Some(1), None]);
+        assert_eq!(scores(false, false), [None, Some(3), Some(1)]);
+        assert_eq!(scores(false, true), [Some(3), Some(1)]);
+    }
+
+    #[test]
+    fn sink_writes_queries_the_streaming_engine_cannot_run() {
+        let df = df!("region" => ["north", "south", "north"], "sales" => [1, 5, 3]).unwrap();
+        let path = std::env::temp_dir().join(format!("polars-cli-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let streamed = df.clone().lazy().filter(col("sales").gt(lit(1)));
+        sink(streamed, path, None, ParquetCompression::Uncompressed).unwrap();
+        let filtered = std::fs::read_to_string(path).unwrap();
+        let grouped = groupby(
+            df.lazy(),
+            &["region".to_string()],
+            &[Aggregation::parse("sum(sales)").unwrap()],
+        );
+        sink(grouped, path, None, ParquetCompression::Uncompressed).unwrap();
+        let summed = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(filtered, "region,sales\nsouth,5\nnorth,3\n");
+        assert_eq!(summed, "region,sum(sales)\nnorth,4\nsouth,5\n");
+    }
+
+    #[test]
+    fn query_joins_and_filters_registered_tables() {
+        let tables = vec![
+            ("c".to_string(), countries()),
+            ("p".to_string(), populations()),
+        ];
+        let sql = "SELECT c.name, p.population FROM c \
+                   JOIN p ON c.code = p.country \
+                   WHERE p.population > 40";
+
+        let result = collect(query(tables, sql).unwrap()).unwrap();
+
+        let expected = df!("name" => ["France"], "population" => [68]).unwrap();
+        assert!(result.equals(&expected), "{}", result);
+        assert!(query(vec![], "SELECT * FROM missing").is_err());
+    }
+
+    #[test]
+    fn join_types() {
+        assert_eq!(parse_join_type("inner").unwrap(), JoinType::Inner);
+        assert_eq!(parse_join_type("LEFT").unwrap(), JoinType::Left);
+        assert_eq!(
+            parse_join_type("outer").unwrap(),
+            JoinType::Outer { coalesce: false }
+        );
+        assert_eq!(parse_join_type("anti").unwrap(), JoinType::Anti);
+        assert_eq!(parse_join_type("semi").unwrap(), JoinType::Semi);
+        assert!(parse_join_type("cross").is_err());
+    }
+}
@@ -1,6 +1,9 @@
-//command-line tool that reads a CSV file and prints the contents of the file as a DataFrame
+//command-line tool that reads a CSV, Parquet, JSON or IPC file and prints the contents of the file as a DataFrame
 mod cli;
 
+use std::cell::RefCell;
+use std::error::Error;
+
 use clap::Parser;
 use cli::{Cli, Commands};
 use polars::prelude::*;
@@ -10,45 +13,149 @@ fn main() {
     std::env::set_var("POLARS_FMT_MAX_ROWS", "-1");
 
     let args = Cli::parse();
-    match args.command {
-        Some(Commands::Print { path, rows }) => {
-            let df = lib::read_csv(&path);
-            println!("{:?}", df.head(Some(rows)));
-        }
-        Some(Commands::Describe { path }) => {
-            let df = lib::read_csv(&path);
-            println!("{:?}", df);
+    if let Err(e) = run(args) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Cli) -> Result<(), Box<dyn Error>> {
+    let options = args.input.options()?;
+    let format = args.input.format;
+    //scan a file, naming it in the error. Nothing is read until the query is collected,
+    //so the files scanned are also named in the errors of the query
+    let inputs = RefCell::new(Vec::new());
+    let scan = |path: &str| {
+        inputs.borrow_mut().push(path.to_string());
+        lib::scan(path, format, &options).map_err(|e| format!("cannot read {}: {}", path, e))
+    };
+    let query_error = |e: PolarsError| format!("cannot read {}: {}", inputs.borrow().join(", "), e);
+
+    //commands producing a dataframe return its query with the number of rows to print
+    let (lf, rows) = match args.command {
+        Some(Commands::Print { path, rows }) => (scan(&path)?, rows),
+        Some(Commands::Describe { path, columns }) => {
+            let lf = scan(&path)?;
+            let lf = if columns.is_empty() {
+                lf
+            } else {
+                lf.select(columns.iter().map(|c| col(c)).collect::<Vec<_>>())
+            };
+            let df = lib::describe(&lib::collect(lf).map_err(query_error)?)?;
+            let rows = df.height();
+            (df.lazy(), rows)
         }
         Some(Commands::Schema { path }) => {
-            let df = lib::read_csv(&path);
-            println!("{:?}", df.schema());
+            print_only(&args.output, "schema")?;
+            println!("{:?}", scan(&path)?.schema().map_err(query_error)?);
+            return Ok(());
         }
         Some(Commands::Shape { path }) => {
-            let df = lib::read_csv(&path);
-            println!("{:?}", df.shape());
+            print_only(&args.output, "shape")?;
+            println!("{:?}", lib::shape(scan(&path)?).map_err(query_error)?);
+            return Ok(());
         }
+        Some(Commands::Filter {
+            predicate,
+            path,
+            rows,
+        }) => (lib::filter(scan(&path)?, &predicate)?, rows),
         Some(Commands::Sort {
             path,
-            year,
+            by,
+            nulls_first,
+            drop_nulls,
+            columns,
+            rows,
+        }) => (
+            lib::sort(scan(&path)?, &by, !nulls_first, &columns, drop_nulls),
+            rows,
+        ),
+        Some(Commands::Groupby {
+            path,
+            by,
+            agg,
+            rows,
+        }) => (lib::groupby(scan(&path)?, &by, &agg), rows),
+        Some(Commands::Join {
+            path,
+            right,
+            on,
+            left_on,
+            right_on,
+            how,
+            suffix,
             rows,
-            order,
         }) => {
-            let df = lib::read_csv(&path);
-            let country_column_name = "Country Name";
-            //select the country column and the year string passed in and return a new dataframe
-            let vs = df.select_series([country_column_name, &year]).unwrap();
-            //convert the Vec<Series> to a DataFrame
-            let df2 = DataFrame::new(vs).unwrap();
-            //drop any rows with null values and return a new dataframe
-            let df2: DataFrame = df2.drop_nulls::<String>(None).unwrap();
-            //sort the dataframe by the year column and by order passed in
-            let df2 = df2.sort([&year], order, true).unwrap();
-
-            //print the first "rows" of the dataframe
-            println!("{:?}", df2.head(Some(rows)));
+            let (left_on, right_on) = if on.is_empty() {
+                (left_on, right_on)
+            } else {
+                (on.clone(), on)
+            };
+            let lf = lib::join(
+                scan(&path)?,
+                scan(&right)?,
+                &left_on,
+                &right_on,
+                how,
+                &suffix,
+            )?;
+            (lf, rows)
+        }
+        Some(Commands::Query {
+            sql,
+            path,
+            tables,
+            rows,
+        }) => {
+            let mut frames = vec![("t".to_string(), scan(&path)?)];
+            for (name, path) in tables {
+                frames.push((name, scan(&path)?));
+            }
+            (lib::query(frames, &sql)?, rows)
+        }
+        Some(Commands::Convert { path }) => {
+            if args.output.output.is_none() {
+                return Err("convert needs an --output file".into());
+            }
+            (scan(&path)?, 0)
         }
         None => {
             println!("No subcommand was used");
+            return Ok(());
+        }
+    };
+
+    //the whole result is streamed to the output file, only the printed rows are computed
+    match &args.output.output {
+        Some(path) => {
+            lib::sink(lf, path, args.output.output_format, args.output.compression).map_err(
+                |e| {
+                    format!(
+                        "cannot write {} from {}: {}",
+                        path,
+                        inputs.borrow().join(", "),
+                        e
+                    )
+                },
+            )?;
+            println!("Wrote {}", path);
         }
+        None => {
+            let df = lib::collect(lf.limit(rows as IdxSize)).map_err(query_error)?;
+            println!("{:?}", df);
+        }
+    }
+    Ok(())
+}
+
+//schema and shape print a summary of the input rather than a dataframe to write
+fn print_only(output: &cli::OutputArgs, command: &str) -> Result<(), String> {
+    match &output.output {
+        Some(_) => Err(format!(
+            "{} prints its result, --output is not supported",
+            command
+        )),
+        None => Ok(()),
     }
 }
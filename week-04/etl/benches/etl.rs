@@ -0,0 +1,204 @@
+// Throughput of each stage over generated datasets, in rows per second.
+// Before a stage is timed, a few runs of it are checked against its target below,
+// the bench fails when a stage uses more heap per row than its target. Throughput
+// depends on the machine, it is printed and only fails the bench with ETL_BENCH_ENFORCE=1.
+// Sizes default to 1K to 10M rows, set ETL_BENCH_SIZES=1000,100000 to change them.
+//
+//     cargo bench
+//     ETL_BENCH_ENFORCE=1 cargo bench
+//
+// Targets, with the baseline measured on one x86-64 core at 1M rows when they were set:
+//
+//     stage                   target rows/s   baseline rows/s   target bytes/row   baseline
+//     extract_transform_load  200M            1.6G              8                  0
+//     summarize               500M            2.0G              0                  0
+//     write_to_csv            5M              13.5M             0                  0
+//     csv_loader              5M              13.5M             0                  0
+//     csv_loader_table        1M              2.6M              128                87
+//
+// Throughput is only enforced from 100K rows, smaller runs are too short to time, and
+// memory is allowed 64 KiB on top of the per row target for buffers.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use etl::{
+    extract_transform_load, summarize, write_to_csv, CleanData, CsvLoader, Loader, RawData, Table,
+};
+
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc;
+
+// Peak heap growth while running `f`, in bytes
+fn peak_memory<T>(f: impl FnOnce() -> T) -> usize {
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    drop(f());
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+fn sizes() -> Vec<usize> {
+    std::env::var("ETL_BENCH_SIZES")
+        .map(|s| {
+            s.split(',')
+                .map(|n| {
+                    n.trim()
+                        .parse()
+                        .expect("ETL_BENCH_SIZES is a list of sizes")
+                })
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![1_000, 10_000, 100_000, 1_000_000, 10_000_000])
+}
+
+// Values spread over -50..150 so a quarter of them gets clamped
+fn raw_data(rows: usize) -> Vec<RawData> {
+    (0..rows)
+        .map(|i| RawData {
+            id: i as u32,
+            value: ((i * 7919) % 200) as i32 - 50,
+        })
+        .collect()
+}
+
+// Stage, rows per second and heap bytes per row it has to meet
+const TARGETS: &[(&str, f64, usize)] = &[
+    ("extract_transform_load", 200e6, 8),
+    ("summarize", 500e6, 0),
+    ("write_to_csv", 5e6, 0),
+    ("csv_loader", 5e6, 0),
+    ("csv_loader_table", 1e6, 128),
+];
+
+const BUFFER_ALLOWANCE: usize = 64 * 1024;
+
+fn enforce_throughput() -> bool {
+    std::env::var("ETL_BENCH_ENFORCE").is_ok_and(|v| v == "1")
+}
+
+// Peak memory of a first run and best time of three, checked against the stage target
+fn check_target<T>(stage: &str, rows: usize, mut f: impl FnMut() -> T) {
+    let &(_, rows_per_sec, bytes_per_row) = TARGETS
+        .iter()
+        .find(|(name, _, _)| *name == stage)
+        .expect("every stage has a target");
+
+    let bytes = peak_memory(&mut f);
+    let best = (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            drop(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or(Duration::ZERO);
+    let throughput = rows as f64 / best.as_secs_f64().max(1e-9);
+
+    println!(
+        "{}/{}: {:.1}M rows/s (target {:.0}M), peak memory {:.1} MiB, {} bytes/row (target {})",
+        stage,
+        rows,
+        throughput / 1e6,
+        rows_per_sec / 1e6,
+        bytes as f64 / (1024.0 * 1024.0),
+        bytes / rows.max(1),
+        bytes_per_row
+    );
+    assert!(
+        bytes <= bytes_per_row * rows + BUFFER_ALLOWANCE,
+        "{} uses more than {} bytes/row",
+        stage,
+        bytes_per_row
+    );
+    assert!(
+        !enforce_throughput() || rows < 100_000 || throughput >= rows_per_sec,
+        "{} is below {:.0}M rows/s",
+        stage,
+        rows_per_sec / 1e6
+    );
+}
+
+fn stages(c: &mut Criterion) {
+    let dir = tempfile::tempdir().expect("Error creating temp dir");
+    let output = dir.path().join("cleaned_data.csv");
+    let output = output.to_str().unwrap();
+
+    for rows in sizes() {
+        let mut group = c.benchmark_group(format!("{} rows", rows));
+        group.throughput(Throughput::Elements(rows as u64));
+        group.sample_size(10);
+
+        // the input is built outside of the checked run, like in the timed one
+        let mut inputs = (0..4).map(|_| raw_data(rows)).collect::<Vec<_>>();
+        check_target("extract_transform_load", rows, || {
+            extract_transform_load(inputs.pop().expect("one input per run"))
+        });
+        group.bench_function(BenchmarkId::new("extract_transform_load", rows), |b| {
+            b.iter_batched(
+                || raw_data(rows),
+                extract_transform_load,
+                BatchSize::LargeInput,
+            )
+        });
+
+        let cleaned: Vec<CleanData> = extract_transform_load(raw_data(rows));
+
+        check_target("summarize", rows, || summarize(&cleaned));
+        group.bench_function(BenchmarkId::new("summarize", rows), |b| {
+            b.iter(|| summarize(&cleaned))
+        });
+
+        check_target("write_to_csv", rows, || {
+            write_to_csv(&cleaned, output).expect("Error writing to CSV")
+        });
+        group.bench_function(BenchmarkId::new("write_to_csv", rows), |b| {
+            b.iter(|| write_to_csv(&cleaned, output).expect("Error writing to CSV"))
+        });
+
+        let load = || {
+            CsvLoader::new(output)
+                .load_cleaned(&cleaned)
+                .expect("Error loading records")
+        };
+        check_target("csv_loader", rows, load);
+        group.bench_function(BenchmarkId::new("csv_loader", rows), |b| b.iter(load));
+
+        // path taken when a privacy policy or row provenance changes the columns
+        let load_table = || {
+            CsvLoader::new(output)
+                .load(&Table::from(cleaned.as_slice()))
+                .expect("Error loading table")
+        };
+        check_target("csv_loader_table", rows, load_table);
+        group.bench_function(BenchmarkId::new("csv_loader_table", rows), |b| {
+            b.iter(load_table)
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, stages);
+criterion_main!(benches);
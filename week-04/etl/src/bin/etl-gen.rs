@@ -0,0 +1,88 @@
+use std::io::stdout;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use etl::generate::{Column, Generator};
+
+#[derive(Parser)]
+#[command(
+    name = "etl-gen",
+    version,
+    about = "Generate a seeded synthetic dataset of raw data, with dirty rows mixed in",
+    after_help = "Example: cargo run --bin etl-gen -- --rows 100000 --out-of-range 0.1 --output raw.csv"
+)]
+struct Args {
+    #[arg(long, default_value = "1000")]
+    rows: usize,
+    #[arg(long, default_value = "0")]
+    seed: u64,
+    /// Column after the id, `name=uniform(min, max)` or `name=normal(mean, std_dev)`.
+    /// Repeat for several columns, `value=uniform(0, 100)` when none is given
+    #[arg(long = "column", value_parser = Column::parse)]
+    columns: Vec<Column>,
+    /// Fraction of rows with a value outside its column range
+    #[arg(long, default_value = "0", value_parser = rate)]
+    out_of_range: f64,
+    /// Fraction of rows reusing an earlier id
+    #[arg(long, default_value = "0", value_parser = rate)]
+    duplicates: f64,
+    /// Fraction of rows with an empty value
+    #[arg(long, default_value = "0", value_parser = rate)]
+    nulls: f64,
+    /// Fraction of rows with a missing or extra field, or text instead of a number
+    #[arg(long, default_value = "0", value_parser = rate)]
+    malformed: f64,
+    #[arg(long, default_value = ";", value_parser = ascii)]
+    delimiter: u8,
+    /// File to write, standard output when not set
+    #[arg(long)]
+    output: Option<String>,
+}
+
+fn rate(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err(format!("expected a fraction between 0 and 1, got {}", text)),
+    }
+}
+
+fn ascii(text: &str) -> Result<u8, String> {
+    match text.parse::<char>() {
+        Ok(c) if c.is_ascii() => Ok(c as u8),
+        _ => Err(format!("expected an ASCII character, got {}", text)),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    // a row has one kind of dirt at most
+    if args.out_of_range + args.duplicates + args.nulls + args.malformed > 1.0 {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the dirty row rates add up to more than 1",
+            )
+            .exit();
+    }
+    let generator = args
+        .columns
+        .into_iter()
+        .fold(Generator::new(args.rows), Generator::column)
+        .seed(args.seed)
+        .delimiter(args.delimiter)
+        .out_of_range(args.out_of_range)
+        .duplicates(args.duplicates)
+        .nulls(args.nulls)
+        .malformed(args.malformed);
+
+    let stats = match &args.output {
+        Some(path) => generator.write_csv(path),
+        None => generator.write(stdout().lock()),
+    }
+    .expect("Error generating data");
+
+    eprintln!(
+        "{}",
+        serde_json::to_string(&stats).expect("Error serializing stats")
+    );
+}
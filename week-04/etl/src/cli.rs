@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::Parser;
+
+const CLEANED_FILE: &str = "cleaned_data.csv";
+const HISTORY_FILE: &str = "cleaned_data_history.csv";
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Clean raw data, summarize it and load it to a CSV file",
+    after_help = "Example: cargo run -- serve --port 8080",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+    /// Keep every version of an id in a history file (SCD type 2), this load being valid
+    /// from the given date, e.g. --scd2 2024-02-01
+    #[arg(long)]
+    pub scd2: Option<NaiveDate>,
+    /// File the SCD type 2 history is kept in, apart from the cleaned file
+    #[arg(long, default_value = HISTORY_FILE, requires = "scd2")]
+    pub scd2_file: String,
+    /// Transforms run instead of clamping to [0, 100], one per line:
+    /// `clamp MIN MAX` or `rule <rule>`, lines starting with # are comments
+    #[arg(long)]
+    pub transforms: Option<PathBuf>,
+    /// Rule run after the other transforms, e.g. --rule "value = value * 2", can be repeated
+    #[arg(long)]
+    pub rule: Vec<String>,
+    /// Also upsert the cleaned records into this key-value store directory, see `etl get`
+    #[arg(long)]
+    pub kv: Option<String>,
+}
+
+#[derive(Parser)]
+pub enum Commands {
+    /// Accept POSTed batches of raw data (JSON or CSV) on /batch and append them to the sink
+    Serve {
+        #[arg(long, default_value = "8080")]
+        port: u16,
+        #[arg(long, default_value = CLEANED_FILE)]
+        sink: String,
+        /// Also upsert every batch into this key-value store directory
+        #[arg(long)]
+        kv: Option<String>,
+    },
+    /// Process files dropped in an inbox directory, moving them to processed/, failed/
+    /// or skipped/ when already processed
+    Watch {
+        dir: String,
+        #[arg(long, default_value = CLEANED_FILE)]
+        sink: String,
+        /// Also upsert every processed file into this key-value store directory
+        #[arg(long)]
+        kv: Option<String>,
+        /// Seconds between two scans of the inbox
+        #[arg(long, default_value = "5")]
+        interval: u64,
+    },
+    /// Print the current record of an id from a key-value store written with --kv
+    Get { store: String, id: u32 },
+}
@@ -0,0 +1,557 @@
+// A small expression language for cleaning rules that Clamp cannot express, e.g.
+//
+//     value = if value < 0 then null else value * 2
+//
+// A rule assigns an expression to `value` or `id`. Expressions use integers, booleans,
+// `null`, the record columns `id` and `value`, arithmetic (+ - * / %), comparisons
+// (< <= > >= == !=), `and`/`or`/`not` and `if .. then .. else ..`.
+// Rules are type checked when parsed, so a bad rule fails before any record is read.
+// Assigning null to `value` drops the record, a runtime error (division by zero,
+// overflow) rejects it.
+use std::fmt;
+
+use crate::transform::Transform;
+use crate::CleanData;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError(pub String);
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Assign,
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| ExprError(format!("integer too large at {}: {}", start, text)))?;
+            tokens.push((start, Token::Int(n)));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+            continue;
+        }
+
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            ('<', Some('=')) => (Token::Op("<="), 2),
+            ('>', Some('=')) => (Token::Op(">="), 2),
+            ('=', Some('=')) => (Token::Op("=="), 2),
+            ('!', Some('=')) => (Token::Op("!="), 2),
+            ('<', _) => (Token::Op("<"), 1),
+            ('>', _) => (Token::Op(">"), 1),
+            ('=', _) => (Token::Assign, 1),
+            ('+', _) => (Token::Op("+"), 1),
+            ('-', _) => (Token::Op("-"), 1),
+            ('*', _) => (Token::Op("*"), 1),
+            ('/', _) => (Token::Op("/"), 1),
+            ('%', _) => (Token::Op("%"), 1),
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            _ => return Err(ExprError(format!("unexpected '{}' at {}", c, start))),
+        };
+        tokens.push((start, token));
+        i += len;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Int(i64),
+    Bool(bool),
+    Null,
+    Column(Column),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+    Id,
+    Value,
+}
+
+impl Column {
+    fn parse(name: &str) -> Option<Column> {
+        match name {
+            "id" => Some(Column::Id),
+            "value" => Some(Column::Value),
+            _ => None,
+        }
+    }
+}
+
+const KEYWORDS: [&str; 9] = [
+    "if", "then", "else", "and", "or", "not", "null", "true", "false",
+];
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.len, |(o, _)| *o)
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, ExprError> {
+        match self.peek() {
+            Some(token) => Err(ExprError(format!(
+                "expected {} at {}, found {:?}",
+                expected,
+                self.offset(),
+                token
+            ))),
+            None => Err(ExprError(format!("expected {} at end of rule", expected))),
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(k)) if k == keyword) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ExprError> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        self.error(&format!("'{}'", keyword))
+    }
+
+    fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_keyword("if") {
+            let cond = self.expr()?;
+            self.expect_keyword("then")?;
+            let then = self.expr()?;
+            self.expect_keyword("else")?;
+            let otherwise = self.expr()?;
+            return Ok(Expr::If(
+                Box::new(cond),
+                Box::new(then),
+                Box::new(otherwise),
+            ));
+        }
+        self.or()
+    }
+
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.and()?;
+        while self.eat_keyword("or") {
+            left = Expr::Binary("or", Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.not()?;
+        while self.eat_keyword("and") {
+            left = Expr::Binary("and", Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExprError> {
+        let left = self.additive()?;
+        match self.eat_op(&["<", "<=", ">", ">=", "==", "!="]) {
+            Some(op) => Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?))),
+            None => Ok(left),
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.multiplicative()?;
+        while let Some(op) = self.eat_op(&["+", "-"]) {
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.eat_op(&["*", "/", "%"]) {
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_op(&["-"]).is_some() {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let offset = self.offset();
+        let expr = match self.peek().cloned() {
+            Some(Token::Int(n)) => Expr::Int(n),
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return self.error("')'");
+                }
+                inner
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "null" => Expr::Null,
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                _ if KEYWORDS.contains(&name.as_str()) => return self.error("an expression"),
+                _ => match Column::parse(&name) {
+                    Some(column) => Expr::Column(column),
+                    None => {
+                        return Err(ExprError(format!("unknown column at {}: {}", offset, name)))
+                    }
+                },
+            },
+            _ => return self.error("an expression"),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Int,
+    Bool,
+    Null,
+    NullableInt,
+}
+
+fn unify(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        (a, b) if a == b => Some(a),
+        (Type::Int | Type::NullableInt, Type::Null | Type::NullableInt)
+        | (Type::Null | Type::NullableInt, Type::Int | Type::NullableInt) => {
+            Some(Type::NullableInt)
+        }
+        _ => None,
+    }
+}
+
+fn type_of(expr: &Expr) -> Result<Type, ExprError> {
+    let expect = |expr: &Expr, expected: Type, context: &str| -> Result<(), ExprError> {
+        let found = type_of(expr)?;
+        if found != expected {
+            return Err(ExprError(format!(
+                "{} expects {:?}, found {:?}",
+                context, expected, found
+            )));
+        }
+        Ok(())
+    };
+
+    match expr {
+        Expr::Int(_) | Expr::Column(_) => Ok(Type::Int),
+        Expr::Bool(_) => Ok(Type::Bool),
+        Expr::Null => Ok(Type::Null),
+        Expr::Neg(inner) => expect(inner, Type::Int, "'-'").map(|_| Type::Int),
+        Expr::Not(inner) => expect(inner, Type::Bool, "'not'").map(|_| Type::Bool),
+        Expr::Binary(op @ ("and" | "or"), left, right) => {
+            expect(left, Type::Bool, &format!("'{}'", op))?;
+            expect(right, Type::Bool, &format!("'{}'", op))?;
+            Ok(Type::Bool)
+        }
+        Expr::Binary(op @ ("==" | "!="), left, right) => {
+            let (l, r) = (type_of(left)?, type_of(right)?);
+            if unify(l, r).is_none() {
+                return Err(ExprError(format!(
+                    "'{}' cannot compare {:?} with {:?}",
+                    op, l, r
+                )));
+            }
+            Ok(Type::Bool)
+        }
+        Expr::Binary(op @ ("<" | "<=" | ">" | ">="), left, right) => {
+            expect(left, Type::Int, &format!("'{}'", op))?;
+            expect(right, Type::Int, &format!("'{}'", op))?;
+            Ok(Type::Bool)
+        }
+        Expr::Binary(op, left, right) => {
+            expect(left, Type::Int, &format!("'{}'", op))?;
+            expect(right, Type::Int, &format!("'{}'", op))?;
+            Ok(Type::Int)
+        }
+        Expr::If(cond, then, otherwise) => {
+            expect(cond, Type::Bool, "'if' condition")?;
+            let (t, o) = (type_of(then)?, type_of(otherwise)?);
+            unify(t, o).ok_or_else(|| {
+                ExprError(format!(
+                    "'if' branches have different types: {:?} and {:?}",
+                    t, o
+                ))
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Int(i64),
+    Bool(bool),
+    Null,
+}
+
+fn eval(expr: &Expr, record: &CleanData) -> Result<Value, String> {
+    let int = |expr: &Expr| match eval(expr, record)? {
+        Value::Int(n) => Ok(n),
+        other => Err(format!("expected an integer, found {:?}", other)),
+    };
+    let boolean = |expr: &Expr| match eval(expr, record)? {
+        Value::Bool(b) => Ok(b),
+        other => Err(format!("expected a boolean, found {:?}", other)),
+    };
+    let overflow = || format!("arithmetic overflow for record {}", record.id);
+
+    Ok(match expr {
+        Expr::Int(n) => Value::Int(*n),
+        Expr::Bool(b) => Value::Bool(*b),
+        Expr::Null => Value::Null,
+        Expr::Column(Column::Id) => Value::Int(record.id as i64),
+        Expr::Column(Column::Value) => Value::Int(record.value as i64),
+        Expr::Neg(inner) => Value::Int(int(inner)?.checked_neg().ok_or_else(overflow)?),
+        Expr::Not(inner) => Value::Bool(!boolean(inner)?),
+        Expr::Binary("and", left, right) => Value::Bool(boolean(left)? && boolean(right)?),
+        Expr::Binary("or", left, right) => Value::Bool(boolean(left)? || boolean(right)?),
+        Expr::Binary("==", left, right) => Value::Bool(eval(left, record)? == eval(right, record)?),
+        Expr::Binary("!=", left, right) => Value::Bool(eval(left, record)? != eval(right, record)?),
+        Expr::Binary(op, left, right) => {
+            let (l, r) = (int(left)?, int(right)?);
+            match *op {
+                "<" => Value::Bool(l < r),
+                "<=" => Value::Bool(l <= r),
+                ">" => Value::Bool(l > r),
+                ">=" => Value::Bool(l >= r),
+                "+" => Value::Int(l.checked_add(r).ok_or_else(overflow)?),
+                "-" => Value::Int(l.checked_sub(r).ok_or_else(overflow)?),
+                "*" => Value::Int(l.checked_mul(r).ok_or_else(overflow)?),
+                "/" | "%" if r == 0 => {
+                    return Err(format!("division by zero for record {}", record.id))
+                }
+                "/" => Value::Int(l.checked_div(r).ok_or_else(overflow)?),
+                "%" => Value::Int(l.checked_rem(r).ok_or_else(overflow)?),
+                _ => unreachable!("unknown operator {}", op),
+            }
+        }
+        Expr::If(cond, then, otherwise) => {
+            if boolean(cond)? {
+                eval(then, record)?
+            } else {
+                eval(otherwise, record)?
+            }
+        }
+    })
+}
+
+// A parsed and type checked rule, usable as a pipeline transform
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    source: String,
+    target: Column,
+    expr: Expr,
+}
+
+impl Rule {
+    pub fn parse(src: &str) -> Result<Rule, ExprError> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            len: src.chars().count(),
+        };
+
+        let target = match parser.peek().cloned() {
+            Some(Token::Ident(name)) => Column::parse(&name)
+                .ok_or_else(|| ExprError(format!("cannot assign to unknown column {}", name)))?,
+            _ => return parser.error("a column to assign"),
+        };
+        parser.pos += 1;
+        if parser.peek() != Some(&Token::Assign) {
+            return parser.error("'='");
+        }
+        parser.pos += 1;
+
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return parser.error("end of rule");
+        }
+
+        match (target, type_of(&expr)?) {
+            (_, Type::Int) | (Column::Value, Type::Null | Type::NullableInt) => {}
+            (column, found) => {
+                return Err(ExprError(format!(
+                    "cannot assign {:?} to {:?}",
+                    found, column
+                )))
+            }
+        }
+
+        Ok(Rule {
+            source: src.trim().to_string(),
+            target,
+            expr,
+        })
+    }
+}
+
+impl Transform for Rule {
+    fn apply(&self, record: CleanData) -> Result<Option<CleanData>, String> {
+        let result = match eval(&self.expr, &record)? {
+            Value::Int(n) => n,
+            Value::Null => return Ok(None),
+            Value::Bool(_) => unreachable!("rules are type checked to produce integers"),
+        };
+
+        let out_of_range = || format!("{} out of range for record {}", result, record.id);
+        Ok(Some(match self.target {
+            Column::Id => CleanData {
+                id: u32::try_from(result).map_err(|_| out_of_range())?,
+                value: record.value,
+            },
+            Column::Value => CleanData {
+                id: record.id,
+                value: i32::try_from(result).map_err(|_| out_of_range())?,
+            },
+        }))
+    }
+
+    fn describe(&self) -> String {
+        self.source.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(rule: &str, id: u32, value: i32) -> Result<Option<(u32, i32)>, String> {
+        let rule = Rule::parse(rule).expect("Error parsing rule");
+        rule.apply(CleanData { id, value })
+            .map(|r| r.map(|r| (r.id, r.value)))
+    }
+
+    #[test]
+    fn if_then_else_with_null() {
+        let rule = "value = if value < 0 then null else value * 2";
+
+        assert_eq!(apply(rule, 1, -5), Ok(None));
+        assert_eq!(apply(rule, 2, 21), Ok(Some((2, 42))));
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(apply("value = 1 + 2 * 3", 1, 0), Ok(Some((1, 7))));
+        assert_eq!(apply("value = (1 + 2) * 3", 1, 0), Ok(Some((1, 9))));
+        assert_eq!(apply("value = -value % 7", 1, 10), Ok(Some((1, -3))));
+        assert_eq!(
+            apply(
+                "value = if not id == 1 and value > 5 or false then 1 else 0",
+                2,
+                10
+            ),
+            Ok(Some((2, 1)))
+        );
+    }
+
+    #[test]
+    fn runtime_errors_reject_the_record() {
+        assert!(apply("value = value / (id - 1)", 1, 10).is_err());
+        assert!(apply("value = value * 1000000000", 1, 10).is_err());
+        assert!(apply("id = value", 1, -1).is_err());
+    }
+
+    #[test]
+    fn type_errors_are_reported_before_the_run() {
+        for rule in [
+            "value = value < 0",
+            "value = if value then 1 else 0",
+            "value = null + 1",
+            "value = if value < 0 then true else 1",
+            "id = if value < 0 then null else value",
+            "value = not 1",
+        ] {
+            assert!(Rule::parse(rule).is_err(), "{} should not type check", rule);
+        }
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        assert_eq!(
+            Rule::parse("value = country * 2"),
+            Err(ExprError("unknown column at 8: country".to_string()))
+        );
+        assert!(Rule::parse("value = (value + 1").is_err());
+        assert!(Rule::parse("value = value +").is_err());
+        assert!(Rule::parse("value value").is_err());
+        assert!(Rule::parse("value = 1 2").is_err());
+        assert!(Rule::parse("value = 1 $ 2").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use crate::lineage::Provenance;
+use crate::RawData;
+
+// Origin of the raw data fed to the pipeline
+pub trait Source {
+    fn extract(&mut self) -> std::io::Result<Vec<RawData>>;
+
+    // Files read by the source, for lineage
+    fn files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    // Origin of every record of the last extract, empty when unknown
+    fn provenance(&self) -> Vec<Provenance> {
+        Vec::new()
+    }
+}
+
+// In memory data is handed over once, a second extract returns nothing
+impl Source for Vec<RawData> {
+    fn extract(&mut self) -> std::io::Result<Vec<RawData>> {
+        Ok(std::mem::take(self))
+    }
+}
+
+// Reads `id;value` rows with a header, ';' by default like write_to_csv
+pub struct CsvSource {
+    path: PathBuf,
+    delimiter: u8,
+    provenance: Vec<Provenance>,
+}
+
+impl CsvSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CsvSource {
+            path: path.into(),
+            delimiter: b';',
+            provenance: Vec::new(),
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+impl Source for CsvSource {
+    fn extract(&mut self) -> std::io::Result<Vec<RawData>> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .delimiter(self.delimiter)
+            .from_path(&self.path)?;
+
+        let headers = rdr.headers()?.clone();
+        let file = self.path.display().to_string();
+        let mut raw = Vec::new();
+        self.provenance.clear();
+        for result in rdr.records() {
+            let record = result?;
+            raw.push(record.deserialize(Some(&headers))?);
+            self.provenance.push(Provenance {
+                file: file.clone(),
+                line: record.position().map_or(0, |p| p.line()),
+            });
+        }
+
+        Ok(raw)
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+
+    fn provenance(&self) -> Vec<Provenance> {
+        self.provenance.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_source_reads_raw_data() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("raw.csv");
+        std::fs::write(&path, "id;value\n1;-5\n2;120\n").expect("Error writing file");
+
+        let mut source = CsvSource::new(&path);
+        let raw = source.extract().expect("Error extracting");
+
+        assert_eq!(raw.len(), 2);
+        assert_eq!(raw[0].id, 1);
+        assert_eq!(raw[0].value, -5);
+        assert_eq!(raw[1].id, 2);
+        assert_eq!(raw[1].value, 120);
+        assert_eq!(source.provenance()[1].line, 3);
+        assert_eq!(source.files(), vec![path]);
+    }
+}
@@ -0,0 +1,457 @@
+// Synthetic `id;value` datasets for testing cleaning rules and benchmarking the pipeline.
+// Output is fully determined by the seed. Dirty rows are mixed in at configured rates:
+// out-of-range values, duplicate ids, nulls (empty cells) and malformed rows.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution as _, Normal};
+use serde::Serialize;
+
+// Distribution of the generated values of a column, written `uniform(min, max)` or
+// `normal(mean, std_dev)`. A normal column stays within 3 standard deviations of the mean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    Uniform { min: i32, max: i32 },
+    Normal { mean: f64, std_dev: f64 },
+}
+
+impl Default for Distribution {
+    // The range kept by Clamp::default
+    fn default() -> Self {
+        Distribution::Uniform { min: 0, max: 100 }
+    }
+}
+
+impl Distribution {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (kind, args) = text
+            .strip_suffix(')')
+            .and_then(|t| t.split_once('('))
+            .ok_or_else(|| format!("expected kind(a, b), got {:?}", text))?;
+        let args: Vec<&str> = args.split(',').map(str::trim).collect();
+        if args.len() != 2 {
+            return Err(format!("{} takes two arguments, got {:?}", kind, text));
+        }
+
+        match kind.trim() {
+            "uniform" => {
+                let min = args[0]
+                    .parse()
+                    .map_err(|_| format!("bad min: {}", args[0]))?;
+                let max = args[1]
+                    .parse()
+                    .map_err(|_| format!("bad max: {}", args[1]))?;
+                if min > max {
+                    return Err(format!("min {} is above max {}", min, max));
+                }
+                Ok(Distribution::Uniform { min, max })
+            }
+            "normal" => {
+                let mean = args[0]
+                    .parse()
+                    .map_err(|_| format!("bad mean: {}", args[0]))?;
+                let std_dev: f64 = args[1]
+                    .parse()
+                    .map_err(|_| format!("bad std_dev: {}", args[1]))?;
+                if !std_dev.is_finite() || std_dev < 0.0 {
+                    return Err(format!("std_dev must not be negative, got {}", std_dev));
+                }
+                Ok(Distribution::Normal { mean, std_dev })
+            }
+            other => Err(format!("unknown distribution: {}", other)),
+        }
+    }
+
+    // Values a clean row can take
+    pub fn range(&self) -> (i32, i32) {
+        match *self {
+            Distribution::Uniform { min, max } => (min, max),
+            Distribution::Normal { mean, std_dev } => {
+                (to_i32(mean - 3.0 * std_dev), to_i32(mean + 3.0 * std_dev))
+            }
+        }
+    }
+
+    fn sample(&self, rng: &mut ChaCha8Rng) -> i32 {
+        let (min, max) = self.range();
+        match *self {
+            Distribution::Uniform { .. } => rng.gen_range(min..=max),
+            Distribution::Normal { mean, std_dev } => {
+                let normal = Normal::new(mean, std_dev).expect("std_dev checked by parse");
+                to_i32(normal.sample(rng)).clamp(min, max)
+            }
+        }
+    }
+
+    // Below or above the range, by up to its width
+    fn out_of_range(&self, rng: &mut ChaCha8Rng) -> i64 {
+        let (min, max) = self.range();
+        let width = (max as i64 - min as i64).max(1);
+        let offset = rng.gen_range(1..=width);
+        if rng.gen_bool(0.5) {
+            min as i64 - offset
+        } else {
+            max as i64 + offset
+        }
+    }
+}
+
+fn to_i32(value: f64) -> i32 {
+    value.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub distribution: Distribution,
+}
+
+impl Column {
+    // `name=distribution`, e.g. `value=normal(50, 15)`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (name, distribution) = text
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=distribution, got {:?}", text))?;
+        Ok(Column {
+            name: name.trim().to_string(),
+            distribution: Distribution::parse(distribution)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dirt {
+    Malformed,
+    Duplicate,
+    Null,
+    OutOfRange,
+}
+
+// What was written, dirty rows are counted once each
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct GenStats {
+    pub rows: usize,
+    pub out_of_range: usize,
+    pub duplicates: usize,
+    pub nulls: usize,
+    pub malformed: usize,
+}
+
+// Rows of an `id` column followed by the configured columns, `value=uniform(0, 100)`
+// unless set, which is the shape of RawData. Rates are fractions of the rows.
+// A row has at most one kind of dirt, malformed rows are drawn first, then duplicates,
+// nulls and out of range values, so when the rates add up to more than 1 the last ones
+// get what is left. A null or out of range row has one such value, in a random column.
+//
+//     Generator::new(1_000).seed(7).out_of_range(0.1).write_csv("raw.csv")?;
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rows: usize,
+    seed: u64,
+    delimiter: u8,
+    columns: Vec<Column>,
+    out_of_range: f64,
+    duplicates: f64,
+    nulls: f64,
+    malformed: f64,
+}
+
+impl Generator {
+    pub fn new(rows: usize) -> Self {
+        Generator {
+            rows,
+            seed: 0,
+            delimiter: b';',
+            columns: Vec::new(),
+            out_of_range: 0.0,
+            duplicates: 0.0,
+            nulls: 0.0,
+            malformed: 0.0,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn column(mut self, column: Column) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    pub fn out_of_range(mut self, rate: f64) -> Self {
+        self.out_of_range = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn duplicates(mut self, rate: f64) -> Self {
+        self.duplicates = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn nulls(mut self, rate: f64) -> Self {
+        self.nulls = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn malformed(mut self, rate: f64) -> Self {
+        self.malformed = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<GenStats> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn write(&self, writer: impl Write) -> std::io::Result<GenStats> {
+        let default = [Column {
+            name: "value".to_string(),
+            distribution: Distribution::default(),
+        }];
+        let columns = if self.columns.is_empty() {
+            &default[..]
+        } else {
+            &self.columns
+        };
+
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_writer(writer);
+        let mut stats = GenStats::default();
+        let mut last_id = 0u32;
+
+        wtr.write_record(std::iter::once("id").chain(columns.iter().map(|c| c.name.as_str())))?;
+        let mut row = Vec::with_capacity(columns.len() + 1);
+        for _ in 0..self.rows {
+            let roll: f64 = rng.gen();
+            let mut threshold = 0.0;
+            let dirt = [
+                (Dirt::Malformed, self.malformed),
+                (Dirt::Duplicate, self.duplicates),
+                (Dirt::Null, self.nulls),
+                (Dirt::OutOfRange, self.out_of_range),
+            ]
+            .into_iter()
+            .find(|(_, rate)| {
+                threshold += rate;
+                roll < threshold
+            })
+            .map(|(dirt, _)| dirt);
+
+            // the first row has no earlier id to reuse and stays clean
+            let id = match dirt {
+                Some(Dirt::Duplicate) if last_id > 0 => {
+                    stats.duplicates += 1;
+                    rng.gen_range(1..=last_id)
+                }
+                _ => {
+                    last_id += 1;
+                    last_id
+                }
+            };
+
+            row.clear();
+            row.push(id.to_string());
+            if dirt == Some(Dirt::Malformed) {
+                stats.malformed += 1;
+                malform(&mut row, columns, &mut rng);
+            } else {
+                let dirty_column = match dirt {
+                    Some(Dirt::Null) => {
+                        stats.nulls += 1;
+                        Some(rng.gen_range(0..columns.len()))
+                    }
+                    Some(Dirt::OutOfRange) => {
+                        stats.out_of_range += 1;
+                        Some(rng.gen_range(0..columns.len()))
+                    }
+                    _ => None,
+                };
+                for (i, column) in columns.iter().enumerate() {
+                    row.push(match dirt {
+                        Some(Dirt::Null) if dirty_column == Some(i) => String::new(),
+                        Some(Dirt::OutOfRange) if dirty_column == Some(i) => {
+                            column.distribution.out_of_range(&mut rng).to_string()
+                        }
+                        _ => column.distribution.sample(&mut rng).to_string(),
+                    });
+                }
+            }
+
+            wtr.write_record(&row)?;
+            stats.rows += 1;
+        }
+
+        wtr.flush()?;
+        Ok(stats)
+    }
+}
+
+// A row that cannot be read back: a missing or extra field, or text in a number
+fn malform(row: &mut Vec<String>, columns: &[Column], rng: &mut ChaCha8Rng) {
+    const TEXT: [&str; 4] = ["n/a", "abc", "12.5.3", "--"];
+    match rng.gen_range(0..4) {
+        // only the id
+        0 => {}
+        1 => {
+            for column in columns {
+                row.push(column.distribution.sample(rng).to_string());
+            }
+            row.push(TEXT[rng.gen_range(0..TEXT.len())].to_string());
+        }
+        2 => {
+            row[0] = TEXT[rng.gen_range(0..TEXT.len())].to_string();
+            for column in columns {
+                row.push(column.distribution.sample(rng).to_string());
+            }
+        }
+        _ => {
+            for _ in columns {
+                row.push(TEXT[rng.gen_range(0..TEXT.len())].to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvSource, Source};
+
+    fn generate(generator: &Generator) -> (String, GenStats) {
+        let mut out = Vec::new();
+        let stats = generator.write(&mut out).expect("Error generating");
+        (String::from_utf8(out).unwrap(), stats)
+    }
+
+    #[test]
+    fn same_seed_same_output() {
+        let generator = Generator::new(200)
+            .seed(7)
+            .out_of_range(0.1)
+            .duplicates(0.1)
+            .nulls(0.05)
+            .malformed(0.05);
+
+        let (a, _) = generate(&generator);
+        let (b, _) = generate(&generator);
+        let (c, _) = generate(&generator.clone().seed(8));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn clean_output_is_raw_data_in_range() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("raw.csv");
+
+        let stats = Generator::new(500)
+            .seed(1)
+            .write_csv(&path)
+            .expect("Error generating");
+        let raw = CsvSource::new(&path).extract().expect("Error extracting");
+
+        assert_eq!(
+            stats,
+            GenStats {
+                rows: 500,
+                ..GenStats::default()
+            }
+        );
+        assert_eq!(raw.len(), 500);
+        assert!(raw.iter().all(|r| (0..=100).contains(&r.value)));
+        assert!(raw.iter().enumerate().all(|(i, r)| r.id as usize == i + 1));
+    }
+
+    #[test]
+    fn dirty_rows_follow_the_rates() {
+        let (out, stats) = generate(
+            &Generator::new(10_000)
+                .seed(3)
+                .out_of_range(0.2)
+                .duplicates(0.1)
+                .nulls(0.05)
+                .malformed(0.02),
+        );
+
+        assert_eq!(out.lines().count(), 10_001);
+        assert!((1_700..2_300).contains(&stats.out_of_range));
+        assert!((800..1_200).contains(&stats.duplicates));
+        assert!((350..650).contains(&stats.nulls));
+        assert!((100..300).contains(&stats.malformed));
+    }
+
+    #[test]
+    fn each_dirty_row_is_counted_once() {
+        // malformed rows may lose their id, which would hide the duplicates of it
+        let (out, stats) = generate(
+            &Generator::new(5_000)
+                .seed(9)
+                .out_of_range(0.3)
+                .duplicates(0.3)
+                .nulls(0.2),
+        );
+
+        let mut seen = std::collections::HashSet::new();
+        let mut counted = GenStats {
+            rows: 5_000,
+            ..GenStats::default()
+        };
+        for line in out.lines().skip(1) {
+            let (id, value) = line.split_once(';').unwrap();
+            let first = seen.insert(id.parse::<u32>().unwrap());
+            match value.parse::<i32>() {
+                Ok(value) if !first => {
+                    assert!((0..=100).contains(&value));
+                    counted.duplicates += 1;
+                }
+                Ok(value) if !(0..=100).contains(&value) => counted.out_of_range += 1,
+                Ok(_) => {}
+                Err(_) => {
+                    assert_eq!(value, "");
+                    counted.nulls += 1;
+                }
+            }
+        }
+        assert_eq!(stats, counted);
+
+        // every row is dirty but the first, which has no id to duplicate
+        let (_, stats) = generate(&Generator::new(5_000).seed(9).duplicates(0.5).malformed(0.5));
+        assert!((4_999..=5_000).contains(&(stats.duplicates + stats.malformed)));
+    }
+
+    #[test]
+    fn schema_and_distributions() {
+        let (out, _) = generate(
+            &Generator::new(1_000)
+                .seed(5)
+                .delimiter(b',')
+                .column(Column::parse("score=normal(50, 10)").unwrap())
+                .column(Column::parse("age = uniform(18, 65)").unwrap()),
+        );
+
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("id,score,age"));
+        for line in lines {
+            let fields: Vec<i32> = line.split(',').map(|f| f.parse().unwrap()).collect();
+            assert!((20..=80).contains(&fields[1]));
+            assert!((18..=65).contains(&fields[2]));
+        }
+        assert!(Distribution::parse("uniform(10, 1)").is_err());
+        assert!(Distribution::parse("poisson(3, 1)").is_err());
+        assert!(Column::parse("value").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::load::Table;
+use crate::{summarize_values, CleanData, Summary, TimedData};
+
+// A row whose columns can be looked up by name and that carries a value to aggregate.
+// CleanData only has `id` and `value`, records with more columns to group on,
+// like a category, implement Record to expose them.
+pub trait Record {
+    fn column(&self, name: &str) -> Option<String>;
+    fn value(&self) -> i32;
+}
+
+impl Record for CleanData {
+    fn column(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id.to_string()),
+            "value" => Some(self.value.to_string()),
+            _ => None,
+        }
+    }
+
+    fn value(&self) -> i32 {
+        self.value
+    }
+}
+
+impl Record for TimedData {
+    fn column(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id.to_string()),
+            "value" => Some(self.value.to_string()),
+            "timestamp" => Some(self.timestamp.to_string()),
+            _ => None,
+        }
+    }
+
+    fn value(&self) -> i32 {
+        self.value
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownColumn(pub String);
+
+impl fmt::Display for UnknownColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown column: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownColumn {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupSummary {
+    pub key: Vec<String>,
+    pub summary: Summary,
+}
+
+// Part of a group key, integers sort numerically and before text
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum KeyPart {
+    Number(i64, String),
+    Text(String),
+}
+
+impl KeyPart {
+    fn new(text: String) -> Self {
+        match text.parse() {
+            Ok(n) => KeyPart::Number(n, text),
+            Err(_) => KeyPart::Text(text),
+        }
+    }
+
+    fn into_text(self) -> String {
+        match self {
+            KeyPart::Number(_, text) | KeyPart::Text(text) => text,
+        }
+    }
+}
+
+// Summarize records per distinct value of the `by` columns, groups ordered by key
+pub fn group_summarize<R: Record>(
+    records: &[R],
+    by: &[&str],
+) -> Result<Vec<GroupSummary>, UnknownColumn> {
+    let mut groups: BTreeMap<Vec<KeyPart>, Vec<i32>> = BTreeMap::new();
+
+    for record in records {
+        let key = by
+            .iter()
+            .map(|name| {
+                record
+                    .column(name)
+                    .map(KeyPart::new)
+                    .ok_or_else(|| UnknownColumn(name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        groups.entry(key).or_default().push(record.value());
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|(key, values)| GroupSummary {
+            key: key.into_iter().map(KeyPart::into_text).collect(),
+            summary: summarize_values(values.into_iter()),
+        })
+        .collect())
+}
+
+// Grouped summary table with the key columns first, ready for any loader
+pub fn grouped_table(groups: &[GroupSummary], by: &[&str]) -> Table {
+    let stats = ["count", "total", "average", "min", "max"];
+    let optional = |v: Option<i32>| v.map(|v| v.to_string()).unwrap_or_default();
+
+    Table {
+        headers: by
+            .iter()
+            .chain(stats.iter())
+            .map(|h| h.to_string())
+            .collect(),
+        rows: groups
+            .iter()
+            .map(|g| {
+                let mut row = g.key.clone();
+                row.push(g.summary.count.to_string());
+                row.push(g.summary.total.to_string());
+                row.push(g.summary.average.to_string());
+                row.push(optional(g.summary.min));
+                row.push(optional(g.summary.max));
+                row
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_summary_per_group() {
+        let cleaned = vec![
+            CleanData { id: 2, value: 20 },
+            CleanData { id: 1, value: 10 },
+            CleanData { id: 2, value: 40 },
+        ];
+
+        let groups = group_summarize(&cleaned, &["id"]).expect("Error grouping");
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, vec!["1"]);
+        assert_eq!(groups[0].summary.count, 1);
+        assert_eq!(groups[1].key, vec!["2"]);
+        assert_eq!(groups[1].summary.total, 60);
+        assert_eq!(groups[1].summary.average, 30.0);
+        assert_eq!(groups[1].summary.min, Some(20));
+        assert_eq!(groups[1].summary.max, Some(40));
+    }
+
+    #[test]
+    fn numeric_keys_are_ordered_as_numbers() {
+        let cleaned: Vec<_> = [10, 2, 1]
+            .into_iter()
+            .map(|id| CleanData { id, value: 0 })
+            .collect();
+
+        let groups = group_summarize(&cleaned, &["id"]).expect("Error grouping");
+
+        let keys: Vec<_> = groups.iter().map(|g| g.key[0].as_str()).collect();
+        assert_eq!(keys, vec!["1", "2", "10"]);
+    }
+
+    struct Sale {
+        region: &'static str,
+        store: u32,
+        amount: i32,
+    }
+
+    impl Record for Sale {
+        fn column(&self, name: &str) -> Option<String> {
+            match name {
+                "region" => Some(self.region.to_string()),
+                "store" => Some(self.store.to_string()),
+                _ => None,
+            }
+        }
+
+        fn value(&self) -> i32 {
+            self.amount
+        }
+    }
+
+    #[test]
+    fn records_group_on_their_own_columns() {
+        let sales = vec![
+            Sale {
+                region: "north",
+                store: 12,
+                amount: 5,
+            },
+            Sale {
+                region: "south",
+                store: 3,
+                amount: 7,
+            },
+            Sale {
+                region: "north",
+                store: 12,
+                amount: 1,
+            },
+            Sale {
+                region: "north",
+                store: 9,
+                amount: 2,
+            },
+        ];
+
+        let groups = group_summarize(&sales, &["region", "store"]).expect("Error grouping");
+
+        let keys: Vec<_> = groups.iter().map(|g| g.key.join("/")).collect();
+        assert_eq!(keys, vec!["north/9", "north/12", "south/3"]);
+        assert_eq!(groups[1].summary.total, 6);
+    }
+
+    #[test]
+    fn totals_do_not_overflow() {
+        let sales = [i32::MAX, i32::MAX, 2]
+            .into_iter()
+            .map(|amount| Sale {
+                region: "north",
+                store: 1,
+                amount,
+            })
+            .collect::<Vec<_>>();
+
+        let groups = group_summarize(&sales, &["region"]).expect("Error grouping");
+
+        assert_eq!(groups[0].summary.total, 2 * i32::MAX as i64 + 2);
+        assert_eq!(
+            groups[0].summary.average,
+            (2 * i32::MAX as i64 + 2) as f64 / 3.0
+        );
+    }
+
+    #[test]
+    fn unknown_key_column_is_an_error() {
+        let cleaned = vec![CleanData { id: 1, value: 10 }];
+
+        let result = group_summarize(&cleaned, &["country"]);
+
+        assert_eq!(result, Err(UnknownColumn("country".to_string())));
+    }
+
+    #[test]
+    fn grouped_table_puts_key_columns_first() {
+        let cleaned = vec![
+            CleanData { id: 1, value: 10 },
+            CleanData { id: 1, value: 30 },
+        ];
+        let groups = group_summarize(&cleaned, &["id"]).expect("Error grouping");
+
+        let table = grouped_table(&groups, &["id"]);
+
+        assert_eq!(
+            table.headers,
+            vec!["id", "count", "total", "average", "min", "max"]
+        );
+        assert_eq!(table.rows, vec![vec!["1", "2", "40", "20", "10", "30"]]);
+    }
+}
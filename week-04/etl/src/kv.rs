@@ -0,0 +1,144 @@
+// Embedded key-value sink: records are upserted by id into an on-disk sled database,
+// so repeated runs keep the current value of every id and a single id is read back
+// without scanning a file. Keys are big-endian ids, values are CleanData as JSON.
+use std::path::{Path, PathBuf};
+
+use crate::load::{Loader, Table};
+use crate::CleanData;
+
+// Clones share the open database, so a server can hand one to each batch pipeline
+#[derive(Clone)]
+pub struct KvLoader {
+    path: PathBuf,
+    db: sled::Db,
+}
+
+impl KvLoader {
+    // Opens the database directory, creating it when missing.
+    // Only one process can have it open at a time.
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let db = sled::open(&path)?;
+        Ok(KvLoader { path, db })
+    }
+
+    pub fn get(&self, id: u32) -> std::io::Result<Option<CleanData>> {
+        match self.db.get(id.to_be_bytes())? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.db.is_empty()
+    }
+
+    // Every record, ordered by id
+    pub fn records(&self) -> std::io::Result<Vec<CleanData>> {
+        self.db
+            .iter()
+            .map(|entry| {
+                let (_, bytes) = entry?;
+                Ok(serde_json::from_slice(&bytes)?)
+            })
+            .collect()
+    }
+
+    // The records of a load are applied together and flushed to disk
+    fn upsert<'a>(&self, cleaned: impl Iterator<Item = &'a CleanData>) -> std::io::Result<()> {
+        let mut batch = sled::Batch::default();
+        for item in cleaned {
+            batch.insert(&item.id.to_be_bytes(), serde_json::to_vec(item)?);
+        }
+        self.db.apply_batch(batch)?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+impl Loader for KvLoader {
+    // Other columns than `id` and `value` are not stored
+    fn load(&mut self, table: &Table) -> std::io::Result<()> {
+        self.upsert(table.cleaned()?.iter())
+    }
+
+    fn load_cleaned(&mut self, cleaned: &[CleanData]) -> std::io::Result<()> {
+        self.upsert(cleaned.iter())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Clamp, Pipeline, PrivacyPolicy, RawData};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn later_loads_upsert_by_id() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let mut loader = KvLoader::open(dir.path().join("store")).expect("Error opening store");
+
+        loader
+            .load_cleaned(&[
+                CleanData { id: 1, value: 10 },
+                CleanData { id: 2, value: 20 },
+            ])
+            .expect("Error loading");
+        loader
+            .load(&Table::from(
+                [
+                    CleanData { id: 2, value: 25 },
+                    CleanData { id: 3, value: 30 },
+                ]
+                .as_slice(),
+            ))
+            .expect("Error loading");
+
+        assert_eq!(loader.len(), 3);
+        assert_eq!(loader.get(2).unwrap().map(|c| c.value), Some(25));
+        assert!(loader.get(4).unwrap().is_none());
+        let ids: Vec<u32> = loader.records().unwrap().iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn store_is_kept_between_runs() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("store");
+
+        for (id, value) in [(1, 150), (1, 40)] {
+            Pipeline::new(vec![RawData { id, value }])
+                .transform(Clamp::default())
+                .sink(KvLoader::open(&path).expect("Error opening store"))
+                .run()
+                .expect("Error running pipeline");
+        }
+
+        let loader = KvLoader::open(&path).expect("Error opening store");
+        assert_eq!(loader.len(), 1);
+        assert_eq!(loader.get(1).unwrap().map(|c| c.value), Some(40));
+    }
+
+    #[test]
+    fn table_without_numeric_value_is_refused() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let mut loader = KvLoader::open(dir.path().join("store")).expect("Error opening store");
+        let table = PrivacyPolicy::new()
+            .mask("value", 0)
+            .apply(&Table::from([CleanData { id: 1, value: 10 }].as_slice()))
+            .unwrap();
+
+        let err = loader.load(&table).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(loader.is_empty());
+    }
+}
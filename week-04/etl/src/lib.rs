@@ -1,6 +1,43 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+pub mod expr;
+pub mod extract;
+pub mod generate;
+pub mod group;
+pub mod kv;
+pub mod lineage;
+pub mod load;
+pub mod pipeline;
+pub mod privacy;
+pub mod scd;
+pub mod serve;
+pub mod sort;
+pub mod temporal;
+pub mod transform;
+pub mod watch;
+pub mod window;
+
+pub use expr::{ExprError, Rule};
+pub use extract::{CsvSource, Source};
+pub use group::{group_summarize, grouped_table, GroupSummary, Record};
+pub use kv::KvLoader;
+pub use lineage::{Lineage, Provenance};
+pub use load::{CsvLoader, Loader, Table};
+pub use pipeline::{
+    write_rejects_to_csv, ErrorPolicy, Pipeline, PipelineError, PipelineReport, Reject,
+};
+pub use privacy::{PrivacyError, PrivacyPolicy, TokenizeError, Tokenizer};
+pub use scd::{load_scd2_csv, ScdLoader, ScdRecord};
+pub use serve::BatchServer;
+pub use sort::{ExternalSort, SortKey};
+pub use temporal::{read_events_csv, RawEvent, TimestampParser, Truncate};
+pub use transform::{parse_transforms, read_transforms, Clamp, Transform};
+pub use watch::Watcher;
+pub use window::{
+    aggregate_windows, windowed_table, InvalidWindow, TimedData, Window, WindowSummary,
+};
+
+#[derive(Deserialize, Debug)]
 pub struct RawData {
     pub id: u32,
     pub value: i32,
@@ -14,33 +51,55 @@ pub struct CleanData {
 
 // Perform ETL process
 pub fn extract_transform_load(raw: Vec<RawData>) -> Vec<CleanData> {
+    let clamp = Clamp::default();
+
     raw.into_iter()
-        .map(|r| {
-            let value = match r.value {
-                v if v < 0 => 0,
-                v if v > 100 => 100,
-                _ => r.value,
-            };
-
-            CleanData { id: r.id, value }
+        .map(|r| CleanData {
+            id: r.id,
+            value: clamp.value(r.value),
         })
         .collect()
 }
 
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct Summary {
-    pub total: i32,
+    pub count: usize,
+    // i64 so a sum of i32 values cannot overflow
+    pub total: i64,
     pub average: f64,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+
+pub fn summarize(cleaned: &[CleanData]) -> Summary {
+    summarize_values(cleaned.iter().map(|x| x.value))
 }
 
-pub fn summarize(cleaned: &Vec<CleanData>) -> Summary {
-    let total = cleaned.iter().fold(0, |acc, x| acc + x.value);
-    let count = cleaned.len() as i32;
+pub(crate) fn summarize_values(values: impl Iterator<Item = i32>) -> Summary {
+    let mut count = 0;
+    let mut total: i64 = 0;
+    let mut min: Option<i32> = None;
+    let mut max: Option<i32> = None;
+
+    for value in values {
+        count += 1;
+        total += value as i64;
+        min = Some(min.map_or(value, |m| m.min(value)));
+        max = Some(max.map_or(value, |m| m.max(value)));
+    }
+
     let average = total as f64 / count as f64;
 
-    Summary { total, average }
+    Summary {
+        count,
+        total,
+        average,
+        min,
+        max,
+    }
 }
 
-pub fn write_to_csv(cleaned: &Vec<CleanData>, filename: &str) -> std::io::Result<()> {
+pub fn write_to_csv(cleaned: &[CleanData], filename: &str) -> std::io::Result<()> {
     let mut wtr = csv::WriterBuilder::new()
         .has_headers(true)
         .delimiter(b';')
@@ -54,9 +113,24 @@ pub fn write_to_csv(cleaned: &Vec<CleanData>, filename: &str) -> std::io::Result
     Ok(())
 }
 
+// write_to_csv followed by the lineage sidecar of the file, `sources` being the files
+// the records were read from and `config` the transforms applied, e.g. Clamp::describe
+pub fn write_to_csv_with_lineage(
+    cleaned: &[CleanData],
+    filename: &str,
+    sources: &[std::path::PathBuf],
+    config: &[String],
+) -> std::io::Result<Lineage> {
+    write_to_csv(cleaned, filename)?;
+    let lineage = Lineage::new(sources, config, cleaned.len())?;
+    lineage.write_sidecar(std::path::Path::new(filename))?;
+    Ok(lineage)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn have_same_count_input_output() {
@@ -125,8 +199,11 @@ mod tests {
 
         let summary = summarize(&cleaned);
 
+        assert_eq!(summary.count, 3);
         assert_eq!(summary.total, 60);
         assert_eq!(summary.average, 20.0);
+        assert_eq!(summary.min, Some(10));
+        assert_eq!(summary.max, Some(30));
     }
 
     #[test]
@@ -137,11 +214,97 @@ mod tests {
             CleanData { id: 3, value: 30 },
         ];
 
-        let file_name = "cleaned_data_test.csv";
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let file_name = dir.path().join("cleaned_data_test.csv");
+        let file_name = file_name.to_str().unwrap();
         write_to_csv(&cleaned, file_name).expect("Error writing to CSV");
 
         let reader = std::fs::read(file_name).expect("Error reading file");
         let reader = String::from_utf8(reader).expect("Error to convert to string");
         assert_eq!(reader, "id;value\n1;10\n2;20\n3;30\n");
     }
+
+    #[test]
+    fn write_to_csv_with_lineage_writes_a_sidecar() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let input = dir.path().join("raw.csv");
+        std::fs::write(&input, "id;value\n1;120\n").expect("Error writing input");
+        let output = dir.path().join("cleaned.csv");
+
+        let lineage = write_to_csv_with_lineage(
+            &[CleanData { id: 1, value: 100 }],
+            output.to_str().unwrap(),
+            &[input],
+            &[Clamp::default().describe()],
+        )
+        .expect("Error writing to CSV");
+
+        assert_eq!(lineage.rows, 1);
+        let sidecar = dir.path().join("cleaned.csv.lineage.json");
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(sidecar).expect("Error reading sidecar"))
+                .expect("Error parsing sidecar");
+        assert_eq!(json["config_hash"], lineage.config_hash.as_str());
+        assert_eq!(
+            json["sources"][0]["sha256"],
+            lineage.sources[0].sha256.as_str()
+        );
+    }
+
+    fn raw_data() -> impl Strategy<Value = Vec<RawData>> {
+        prop::collection::vec(
+            (any::<u32>(), any::<i32>()).prop_map(|(id, value)| RawData { id, value }),
+            0..200,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn output_length_is_input_length(raw in raw_data()) {
+            let len = raw.len();
+
+            let cleaned = extract_transform_load(raw);
+
+            prop_assert_eq!(cleaned.len(), len);
+        }
+
+        #[test]
+        fn output_length_is_input_length_minus_rejects(raw in raw_data()) {
+            let len = raw.len();
+            let zeros = raw.iter().filter(|r| r.value % 7 == 0).count();
+
+            // a remainder of zero is a division by zero, the record is rejected
+            let report = Pipeline::new(raw)
+                .transform(Rule::parse("value = value / (value % 7)").unwrap())
+                .transform(Clamp::default())
+                .error_policy(ErrorPolicy::Reject)
+                .run()
+                .expect("Error running pipeline");
+
+            prop_assert_eq!(report.rejects.len(), zeros);
+            prop_assert_eq!(report.cleaned.len(), len - report.rejects.len());
+            prop_assert!(report.cleaned.iter().all(|c| (0..=100).contains(&c.value)));
+        }
+
+        #[test]
+        fn every_value_within_bounds(raw in raw_data()) {
+            let cleaned = extract_transform_load(raw);
+
+            prop_assert!(cleaned.iter().all(|c| (0..=100).contains(&c.value)));
+        }
+
+        #[test]
+        fn ids_and_in_range_values_are_kept(raw in raw_data()) {
+            let expected: Vec<_> = raw.iter().map(|r| (r.id, r.value)).collect();
+
+            let cleaned = extract_transform_load(raw);
+
+            for (c, (id, value)) in cleaned.iter().zip(expected) {
+                prop_assert_eq!(c.id, id);
+                if (0..=100).contains(&value) {
+                    prop_assert_eq!(c.value, value);
+                }
+            }
+        }
+    }
 }
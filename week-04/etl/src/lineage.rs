@@ -0,0 +1,122 @@
+// Lineage metadata written next to every output as `<output>.lineage.json`:
+// which files were read (with their SHA-256), a hash of the transform configuration,
+// the crate version and a run id, so an output row can be traced back to its input.
+// The pipeline writes it when asked to, write_to_csv_with_lineage for a file written
+// without a pipeline. The etl binary writes it for every output.
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// Where a record was read from, `line` is 1-based and counts the header
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub file: String,
+    pub line: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Lineage {
+    pub run_id: String,
+    pub crate_version: String,
+    pub created_at: NaiveDateTime,
+    pub config_hash: String,
+    pub sources: Vec<SourceFile>,
+    pub rows: usize,
+}
+
+impl Lineage {
+    // `config` describes every setting that changes the output, one entry per stage
+    pub fn new(sources: &[PathBuf], config: &[String], rows: usize) -> std::io::Result<Self> {
+        let created_at = Utc::now();
+        let sources = sources
+            .iter()
+            .map(|path| {
+                Ok(SourceFile {
+                    path: path.display().to_string(),
+                    sha256: file_sha256(path)?,
+                })
+            })
+            .collect::<std::io::Result<_>>()?;
+
+        Ok(Lineage {
+            run_id: format!(
+                "{}-{}",
+                created_at.format("%Y%m%dT%H%M%S%.6f"),
+                std::process::id()
+            ),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: created_at.naive_utc(),
+            config_hash: hex(&Sha256::digest(config.join("\n").as_bytes())),
+            sources,
+            rows,
+        })
+    }
+
+    pub fn sidecar_path(output: &Path) -> PathBuf {
+        let mut name = output.file_name().unwrap_or_default().to_os_string();
+        name.push(".lineage.json");
+        output.with_file_name(name)
+    }
+
+    pub fn write_sidecar(&self, output: &Path) -> std::io::Result<()> {
+        let file = File::create(Self::sidecar_path(output))?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_records_sources_and_config() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let input = dir.path().join("raw.csv");
+        let output = dir.path().join("cleaned.csv");
+        std::fs::write(&input, "abc").unwrap();
+
+        let lineage = Lineage::new(&[input], &["clamp(0, 100)".to_string()], 3).unwrap();
+        lineage
+            .write_sidecar(&output)
+            .expect("Error writing lineage");
+
+        assert_eq!(
+            lineage.sources[0].sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(lineage.crate_version, env!("CARGO_PKG_VERSION"));
+        let sidecar = dir.path().join("cleaned.csv.lineage.json");
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(sidecar).unwrap()).unwrap();
+        assert_eq!(json["rows"], 3);
+        assert_eq!(json["config_hash"], lineage.config_hash.as_str());
+    }
+
+    #[test]
+    fn config_hash_changes_with_config() {
+        let a = Lineage::new(&[], &["clamp(0, 100)".to_string()], 0).unwrap();
+        let b = Lineage::new(&[], &["clamp(0, 50)".to_string()], 0).unwrap();
+
+        assert_ne!(a.config_hash, b.config_hash);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::CleanData;
+
+// Rows ready to be written by a loader, every value already formatted as text
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl From<&[CleanData]> for Table {
+    fn from(cleaned: &[CleanData]) -> Self {
+        Table {
+            headers: vec!["id".to_string(), "value".to_string()],
+            rows: cleaned
+                .iter()
+                .map(|c| vec![c.id.to_string(), c.value.to_string()])
+                .collect(),
+        }
+    }
+}
+
+impl Table {
+    // Reads the `id` and `value` columns back, for loaders storing cleaned records
+    pub(crate) fn cleaned(&self) -> std::io::Result<Vec<CleanData>> {
+        let column = |name: &str| {
+            self.headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("no {} column", name)))
+        };
+        let (id, value) = (column("id")?, column("value")?);
+
+        self.rows
+            .iter()
+            .map(|row| {
+                Ok(CleanData {
+                    id: parse(&row[id], "id")?,
+                    value: parse(&row[value], "value")?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse<T: std::str::FromStr>(text: &str, column: &str) -> std::io::Result<T> {
+    text.parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is not a number: {:?}", column, text),
+        )
+    })
+}
+
+// Destination of a table produced by the pipeline
+pub trait Loader {
+    fn load(&mut self, table: &Table) -> std::io::Result<()>;
+
+    // Cleaned records written as they are, loaders able to skip the Table
+    // and its per cell strings override it
+    fn load_cleaned(&mut self, cleaned: &[CleanData]) -> std::io::Result<()> {
+        self.load(&Table::from(cleaned))
+    }
+
+    // File written by the loader, its lineage sidecar goes next to it
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+// Overwrites a delimited file, ';' by default like write_to_csv.
+// In append mode rows are added at the end and the header is only written to a new file.
+pub struct CsvLoader {
+    path: PathBuf,
+    delimiter: u8,
+    append: bool,
+}
+
+impl CsvLoader {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CsvLoader {
+            path: path.into(),
+            delimiter: b';',
+            append: false,
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    // Writer on the file and whether the header is still to be written
+    fn writer(&self) -> std::io::Result<(csv::Writer<File>, bool)> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let wtr = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(false)
+            .from_writer(file);
+        Ok((wtr, is_new))
+    }
+}
+
+impl Loader for CsvLoader {
+    fn load(&mut self, table: &Table) -> std::io::Result<()> {
+        let (mut wtr, is_new) = self.writer()?;
+        if is_new {
+            wtr.write_record(&table.headers)?;
+        }
+        for row in &table.rows {
+            wtr.write_record(row)?;
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+
+    fn load_cleaned(&mut self, cleaned: &[CleanData]) -> std::io::Result<()> {
+        let (mut wtr, is_new) = self.writer()?;
+        if is_new {
+            wtr.write_record(["id", "value"])?;
+        }
+        for item in cleaned {
+            wtr.serialize(item)?;
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_loader_writes_same_output_as_write_to_csv() {
+        let cleaned = vec![
+            CleanData { id: 1, value: 10 },
+            CleanData { id: 2, value: 20 },
+        ];
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("cleaned.csv");
+
+        CsvLoader::new(&path)
+            .load(&Table::from(cleaned.as_slice()))
+            .expect("Error loading table");
+
+        let reader = std::fs::read_to_string(&path).expect("Error reading file");
+        assert_eq!(reader, "id;value\n1;10\n2;20\n");
+    }
+
+    #[test]
+    fn csv_loader_append_writes_header_once() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("cleaned.csv");
+        let first = vec![CleanData { id: 1, value: 10 }];
+        let second = vec![CleanData { id: 2, value: 20 }];
+
+        let mut loader = CsvLoader::new(&path).append(true);
+        loader
+            .load(&Table::from(first.as_slice()))
+            .expect("Error loading table");
+        loader
+            .load(&Table::from(second.as_slice()))
+            .expect("Error loading table");
+
+        let reader = std::fs::read_to_string(&path).expect("Error reading file");
+        assert_eq!(reader, "id;value\n1;10\n2;20\n");
+    }
+
+    #[test]
+    fn load_cleaned_matches_table_load() {
+        let cleaned = vec![
+            CleanData { id: 1, value: 10 },
+            CleanData { id: 2, value: 20 },
+        ];
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let from_table = dir.path().join("table.csv");
+        let direct = dir.path().join("direct.csv");
+
+        CsvLoader::new(&from_table)
+            .delimiter(b',')
+            .load(&Table::from(cleaned.as_slice()))
+            .expect("Error loading table");
+        let mut loader = CsvLoader::new(&direct).delimiter(b',').append(true);
+        loader.load_cleaned(&cleaned[..1]).expect("Error loading");
+        loader.load_cleaned(&cleaned[1..]).expect("Error loading");
+
+        assert_eq!(
+            std::fs::read_to_string(&direct).expect("Error reading file"),
+            std::fs::read_to_string(&from_table).expect("Error reading file")
+        );
+    }
+}
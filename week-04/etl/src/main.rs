@@ -1,6 +1,96 @@
-use etl::{extract_transform_load, summarize, write_to_csv, RawData};
+mod cli;
+
+use chrono::NaiveDate;
+use clap::Parser;
+use cli::{Cli, Commands};
+use std::time::Duration;
+
+use etl::{
+    read_transforms, BatchServer, Clamp, CsvLoader, KvLoader, Pipeline, RawData, Rule, ScdLoader,
+    Transform, Watcher,
+};
 
 fn main() {
+    let args = Cli::parse();
+    match args.command {
+        Some(Commands::Serve { port, sink, kv }) => {
+            let addr = format!("127.0.0.1:{}", port);
+            let mut server = BatchServer::bind(&addr, &sink).expect("Error starting server");
+            if let Some(store) = kv {
+                server = server.kv(KvLoader::open(store).expect("Error opening store"));
+            }
+            println!("Listening on http://{}/batch, appending to {}", addr, sink);
+            server.run().expect("Error serving batches");
+        }
+        Some(Commands::Watch {
+            dir,
+            sink,
+            kv,
+            interval,
+        }) => {
+            let mut watcher = Watcher::new(&dir, &sink)
+                .expect("Error opening inbox")
+                .poll_interval(Duration::from_secs(interval));
+            if let Some(store) = kv {
+                watcher = watcher.kv(KvLoader::open(store).expect("Error opening store"));
+            }
+            println!("Watching {}, appending to {}", dir, sink);
+            watcher.run().expect("Error watching inbox");
+        }
+        Some(Commands::Get { store, id }) => {
+            // opening would create an empty store
+            if !std::path::Path::new(&store).exists() {
+                eprintln!("No store at {}", store);
+                std::process::exit(1);
+            }
+            let store = KvLoader::open(&store).expect("Error opening store");
+            match store.get(id).expect("Error reading store") {
+                Some(record) => println!(
+                    "{}",
+                    serde_json::to_string(&record).expect("Error serializing record")
+                ),
+                None => {
+                    eprintln!("No record for id {}", id);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => {
+            // a bad rule is reported before any record is read
+            let transforms = match transforms(&args) {
+                Ok(transforms) => transforms,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            run_sample(
+                transforms,
+                args.scd2.map(|load_date| (args.scd2_file, load_date)),
+                args.kv,
+            )
+        }
+    }
+}
+
+// Transforms of the config file, or the default clamp, followed by the --rule ones
+fn transforms(args: &Cli) -> Result<Vec<Box<dyn Transform>>, String> {
+    let mut transforms = match &args.transforms {
+        Some(path) => read_transforms(path).map_err(|e| e.to_string())?,
+        None => vec![Box::new(Clamp::default()) as Box<dyn Transform>],
+    };
+    for rule in &args.rule {
+        let rule = Rule::parse(rule).map_err(|e| format!("rule {:?}: {}", rule, e))?;
+        transforms.push(Box::new(rule));
+    }
+    Ok(transforms)
+}
+
+fn run_sample(
+    transforms: Vec<Box<dyn Transform>>,
+    scd2: Option<(String, NaiveDate)>,
+    kv: Option<String>,
+) {
     let raw = vec![
         RawData { id: 1, value: 10 },
         RawData { id: 2, value: -5 },
@@ -12,18 +102,30 @@ fn main() {
         RawData { id: 8, value: 51 },
     ];
 
-    let cleaned = extract_transform_load(raw);
+    let file_name = "cleaned_data.csv";
+    let pipeline = Pipeline::new(raw).transforms(transforms).lineage(true);
+    let pipeline = match scd2 {
+        Some((history_file, load_date)) => pipeline.sink(ScdLoader::new(history_file, load_date)),
+        None => pipeline.sink(CsvLoader::new(file_name)),
+    };
+    let mut pipeline = match kv {
+        Some(store) => pipeline.sink(KvLoader::open(store).expect("Error opening store")),
+        None => pipeline,
+    };
+    let report = match pipeline.run() {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error running ETL pipeline: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    for item in &cleaned {
+    for item in &report.cleaned {
         println!("Clean Data: Id - {:?} Value - {:?}", item.id, item.value); // Accessing the fields
     }
 
-    let summary = summarize(&cleaned);
     println!(
         "Cleaned Data summarize - total: {} - average: {}",
-        summary.total, summary.average
+        report.summary.total, report.summary.average
     );
-
-    let file_name = "cleaned_data.csv";
-    write_to_csv(&cleaned, file_name).expect("Error writing to CSV");
 }
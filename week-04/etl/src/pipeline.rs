@@ -0,0 +1,464 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::extract::Source;
+use crate::lineage::{Lineage, Provenance};
+use crate::load::{Loader, Table};
+use crate::privacy::{PrivacyError, PrivacyPolicy};
+use crate::sort::ExternalSort;
+use crate::transform::Transform;
+use crate::{summarize, CleanData, Summary};
+
+// What to do when a transform rejects a record
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorPolicy {
+    // Stop the run on the first rejected record, nothing is loaded
+    #[default]
+    Abort,
+    // Keep the record out of the output and report it
+    Reject,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Reject {
+    pub id: u32,
+    pub value: i32,
+    pub reason: String,
+}
+
+// Reject output, one row per rejected record with the reason
+pub fn write_rejects_to_csv(rejects: &[Reject], filename: &str) -> std::io::Result<()> {
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_path(filename)?;
+
+    for item in rejects {
+        wtr.serialize(item)?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Io(std::io::Error),
+    Rejected(Reject),
+    Privacy(PrivacyError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Io(err) => write!(f, "{}", err),
+            PipelineError::Rejected(r) => {
+                write!(
+                    f,
+                    "record {} (value {}) rejected: {}",
+                    r.id, r.value, r.reason
+                )
+            }
+            PipelineError::Privacy(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<std::io::Error> for PipelineError {
+    fn from(err: std::io::Error) -> Self {
+        PipelineError::Io(err)
+    }
+}
+
+pub type SummaryHook = Box<dyn FnMut(&Summary)>;
+
+#[derive(Debug)]
+pub struct PipelineReport {
+    pub extracted: usize,
+    pub cleaned: Vec<CleanData>,
+    pub rejects: Vec<Reject>,
+    pub summary: Summary,
+    pub lineage: Option<Lineage>,
+}
+
+// Extract from a source, apply the transforms in order, then write to every sink.
+//
+//     let report = Pipeline::new(CsvSource::new("raw.csv"))
+//         .transform(Clamp::default())
+//         .sink(CsvLoader::new("cleaned_data.csv"))
+//         .error_policy(ErrorPolicy::Reject)
+//         .run()?;
+pub struct Pipeline {
+    source: Box<dyn Source>,
+    transforms: Vec<Box<dyn Transform>>,
+    sort: Option<ExternalSort>,
+    privacy: Option<PrivacyPolicy>,
+    sinks: Vec<Box<dyn Loader>>,
+    summary_hooks: Vec<SummaryHook>,
+    error_policy: ErrorPolicy,
+    lineage: bool,
+    row_provenance: bool,
+}
+
+impl Pipeline {
+    pub fn new(source: impl Source + 'static) -> Self {
+        Pipeline {
+            source: Box::new(source),
+            transforms: Vec::new(),
+            sort: None,
+            privacy: None,
+            sinks: Vec::new(),
+            summary_hooks: Vec::new(),
+            error_policy: ErrorPolicy::default(),
+            lineage: false,
+            row_provenance: false,
+        }
+    }
+
+    pub fn transform(mut self, transform: impl Transform + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    // Transforms built at runtime, e.g. by read_transforms
+    pub fn transforms(mut self, transforms: impl IntoIterator<Item = Box<dyn Transform>>) -> Self {
+        self.transforms.extend(transforms);
+        self
+    }
+
+    // Order the cleaned records before they reach the sinks. The pipeline holds every
+    // record in memory, so the sort has no memory budget: sort a written file larger
+    // than memory with ExternalSort::sort_csv instead.
+    pub fn sort(mut self, sort: ExternalSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    // Hash, mask, tokenize or drop columns before the table reaches any sink.
+    // The report still holds the cleaned records as they were before the policy.
+    pub fn privacy(mut self, policy: PrivacyPolicy) -> Self {
+        self.privacy = Some(policy);
+        self
+    }
+
+    pub fn sink(mut self, sink: impl Loader + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    pub fn on_summary(mut self, hook: impl FnMut(&Summary) + 'static) -> Self {
+        self.summary_hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    // Write a `.lineage.json` sidecar next to every sink writing a file.
+    // An appending sink gets the lineage of the last run appending to it
+    pub fn lineage(mut self, lineage: bool) -> Self {
+        self.lineage = lineage;
+        self
+    }
+
+    // Add `source_file` and `source_line` columns to the output, left empty when the
+    // source does not know where a record comes from
+    pub fn row_provenance(mut self, row_provenance: bool) -> Self {
+        self.row_provenance = row_provenance;
+        self
+    }
+
+    pub fn run(&mut self) -> Result<PipelineReport, PipelineError> {
+        if self.sort.as_ref().is_some_and(|s| s.has_memory_budget()) {
+            return Err(PipelineError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the sort stage sorts in memory, use ExternalSort::sort_csv for a memory budget",
+            )));
+        }
+
+        let raw = self.source.extract()?;
+        let extracted = raw.len();
+        let mut provenance = self.source.provenance().into_iter();
+
+        // records are kept with their provenance until the table is built
+        let mut kept: Vec<(CleanData, Option<Provenance>)> = Vec::with_capacity(extracted);
+        let mut rejects = Vec::new();
+        for r in raw {
+            let origin = provenance.next();
+            match self.apply_transforms(CleanData {
+                id: r.id,
+                value: r.value,
+            }) {
+                Ok(Some(record)) => kept.push((record, origin)),
+                Ok(None) => {}
+                Err(reason) => {
+                    let reject = Reject {
+                        id: r.id,
+                        value: r.value,
+                        reason,
+                    };
+                    match self.error_policy {
+                        ErrorPolicy::Abort => return Err(PipelineError::Rejected(reject)),
+                        ErrorPolicy::Reject => rejects.push(reject),
+                    }
+                }
+            }
+        }
+
+        if let Some(sort) = &self.sort {
+            sort.sort_by_record(&mut kept, |(record, _)| record);
+        }
+        let (cleaned, origins): (Vec<_>, Vec<_>) = kept.into_iter().unzip();
+
+        // the table is only built when columns change, otherwise sinks write the records
+        if self.privacy.is_none() && !self.row_provenance {
+            for sink in self.sinks.iter_mut() {
+                sink.load_cleaned(&cleaned)?;
+            }
+        } else {
+            let mut table = Table::from(cleaned.as_slice());
+            if let Some(policy) = &self.privacy {
+                table = policy.apply(&table).map_err(PipelineError::Privacy)?;
+            }
+            if self.row_provenance {
+                table.headers.push("source_file".to_string());
+                table.headers.push("source_line".to_string());
+                for (row, origin) in table.rows.iter_mut().zip(origins) {
+                    let (file, line) = origin.map_or((String::new(), String::new()), |o| {
+                        (o.file, o.line.to_string())
+                    });
+                    row.push(file);
+                    row.push(line);
+                }
+            }
+            for sink in self.sinks.iter_mut() {
+                sink.load(&table)?;
+            }
+        }
+
+        let lineage = if self.lineage {
+            let lineage = Lineage::new(&self.source.files(), &self.config(), cleaned.len())?;
+            for sink in &self.sinks {
+                if let Some(path) = sink.path() {
+                    lineage.write_sidecar(path)?;
+                }
+            }
+            Some(lineage)
+        } else {
+            None
+        };
+
+        let summary = summarize(&cleaned);
+        for hook in self.summary_hooks.iter_mut() {
+            hook(&summary);
+        }
+
+        Ok(PipelineReport {
+            extracted,
+            cleaned,
+            rejects,
+            summary,
+            lineage,
+        })
+    }
+
+    fn config(&self) -> Vec<String> {
+        let mut config: Vec<_> = self.transforms.iter().map(|t| t.describe()).collect();
+        config.extend(self.sort.as_ref().map(|s| s.describe()));
+        config.extend(self.privacy.as_ref().map(|p| p.describe()));
+        config.push(format!("{:?}", self.error_policy));
+        config.push(format!("row_provenance={}", self.row_provenance));
+        config
+    }
+
+    fn apply_transforms(&self, record: CleanData) -> Result<Option<CleanData>, String> {
+        let mut record = record;
+        for transform in &self.transforms {
+            match transform.apply(record)? {
+                Some(next) => record = next,
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::transform::Clamp;
+    use crate::{extract_transform_load, CsvLoader, CsvSource, RawData};
+
+    fn raw() -> Vec<RawData> {
+        vec![
+            RawData { id: 1, value: -11 },
+            RawData { id: 2, value: 22 },
+            RawData { id: 3, value: 133 },
+        ]
+    }
+
+    fn reject_odd_ids(record: CleanData) -> Result<Option<CleanData>, String> {
+        if record.id % 2 == 1 {
+            Err("odd id".to_string())
+        } else {
+            Ok(Some(record))
+        }
+    }
+
+    #[test]
+    fn clamp_pipeline_matches_extract_transform_load() {
+        let report = Pipeline::new(raw())
+            .transform(Clamp::default())
+            .run()
+            .expect("Error running pipeline");
+
+        let expected = extract_transform_load(raw());
+        assert_eq!(report.extracted, 3);
+        assert_eq!(report.cleaned.len(), expected.len());
+        for (c, e) in report.cleaned.iter().zip(expected.iter()) {
+            assert_eq!((c.id, c.value), (e.id, e.value));
+        }
+        assert_eq!(report.summary.total, 122);
+    }
+
+    #[test]
+    fn transforms_run_in_order() {
+        let double = |r: CleanData| -> Result<Option<CleanData>, String> {
+            Ok(Some(CleanData {
+                id: r.id,
+                value: r.value * 2,
+            }))
+        };
+
+        let report = Pipeline::new(raw())
+            .transform(Clamp::default())
+            .transform(double)
+            .run()
+            .expect("Error running pipeline");
+
+        let values: Vec<_> = report.cleaned.iter().map(|c| c.value).collect();
+        assert_eq!(values, vec![0, 44, 200]);
+    }
+
+    #[test]
+    fn sort_stage_orders_the_output() {
+        let sort = ExternalSort::new(vec![crate::SortKey::desc("value")]).unwrap();
+
+        let report = Pipeline::new(raw())
+            .transform(Clamp::default())
+            .sort(sort)
+            .run()
+            .expect("Error running pipeline");
+
+        let ids: Vec<_> = report.cleaned.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn sort_stage_with_memory_budget_is_refused() {
+        let sort = ExternalSort::new(vec![crate::SortKey::asc("id")])
+            .unwrap()
+            .memory_budget(1024);
+
+        let result = Pipeline::new(raw()).sort(sort).run();
+
+        assert!(
+            matches!(result, Err(PipelineError::Io(err)) if err.kind() == std::io::ErrorKind::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn privacy_policy_applies_before_sinks() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("cleaned.csv");
+
+        let report = Pipeline::new(raw())
+            .transform(Clamp::default())
+            .privacy(PrivacyPolicy::new().drop("id"))
+            .sink(CsvLoader::new(&path))
+            .run()
+            .expect("Error running pipeline");
+
+        assert_eq!(report.cleaned[0].id, 1);
+        let written = std::fs::read_to_string(&path).expect("Error reading file");
+        assert_eq!(written, "value\n0\n22\n100\n");
+    }
+
+    #[test]
+    fn lineage_sidecar_and_row_provenance() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let input = dir.path().join("raw.csv");
+        let output = dir.path().join("cleaned.csv");
+        std::fs::write(&input, "id;value\n1;-11\n2;22\n3;133\n").unwrap();
+        let sort = ExternalSort::new(vec![crate::SortKey::desc("id")]).unwrap();
+
+        let report = Pipeline::new(CsvSource::new(&input))
+            .transform(Clamp::default())
+            .transform(reject_odd_ids)
+            .sort(sort)
+            .sink(CsvLoader::new(&output))
+            .error_policy(ErrorPolicy::Reject)
+            .lineage(true)
+            .row_provenance(true)
+            .run()
+            .expect("Error running pipeline");
+
+        let written = std::fs::read_to_string(&output).expect("Error reading file");
+        assert_eq!(
+            written,
+            format!(
+                "id;value;source_file;source_line\n2;22;{};3\n",
+                input.display()
+            )
+        );
+        let lineage = report.lineage.expect("lineage requested");
+        assert_eq!(lineage.rows, 1);
+        assert_eq!(lineage.sources[0].path, input.display().to_string());
+        assert!(dir.path().join("cleaned.csv.lineage.json").exists());
+    }
+
+    #[test]
+    fn abort_policy_stops_on_first_reject() {
+        let result = Pipeline::new(raw()).transform(reject_odd_ids).run();
+
+        match result {
+            Err(PipelineError::Rejected(r)) => assert_eq!(r.id, 1),
+            other => panic!("expected a rejected record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_policy_reports_rejects_and_loads_the_rest() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("cleaned.csv");
+        let totals = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&totals);
+
+        let report = Pipeline::new(raw())
+            .transform(reject_odd_ids)
+            .sink(CsvLoader::new(&path))
+            .on_summary(move |s| seen.borrow_mut().push(s.total))
+            .error_policy(ErrorPolicy::Reject)
+            .run()
+            .expect("Error running pipeline");
+
+        assert_eq!(
+            report.cleaned.len(),
+            report.extracted - report.rejects.len()
+        );
+        assert_eq!(report.rejects.len(), 2);
+        assert_eq!(report.rejects[0].reason, "odd id");
+        assert_eq!(*totals.borrow(), vec![22]);
+        let written = std::fs::read_to_string(&path).expect("Error reading file");
+        assert_eq!(written, "id;value\n2;22\n");
+    }
+}
@@ -0,0 +1,446 @@
+// Per column privacy rules applied to the table before any sink writes it:
+// salted hashing, partial masking, format preserving tokenization and dropping.
+use std::fmt;
+use std::path::Path;
+
+use aes::Aes256;
+use fpe::ff1::{FlexibleNumeralString, FF1};
+use hmac::{Hmac, Mac};
+use num_bigint::BigUint;
+use sha2::Sha256;
+
+use crate::group::UnknownColumn;
+use crate::lineage::hex;
+use crate::load::Table;
+
+#[derive(Clone)]
+enum Action {
+    Hash { salt: String },
+    Mask { keep_last: usize },
+    Tokenize(Tokenizer),
+    Drop,
+}
+
+// Keep the salt out of logs, like the tokenizer key
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Hash { .. } => f.debug_struct("Hash").finish_non_exhaustive(),
+            Action::Mask { keep_last } => f
+                .debug_struct("Mask")
+                .field("keep_last", keep_last)
+                .finish(),
+            Action::Tokenize(tokenizer) => f.debug_tuple("Tokenize").field(tokenizer).finish(),
+            Action::Drop => f.write_str("Drop"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TokenizeError(pub String);
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+#[derive(Debug, PartialEq)]
+pub enum PrivacyError {
+    UnknownColumn(UnknownColumn),
+    Tokenize(TokenizeError),
+}
+
+impl fmt::Display for PrivacyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrivacyError::UnknownColumn(err) => write!(f, "{}", err),
+            PrivacyError::Tokenize(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PrivacyError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyPolicy {
+    rules: Vec<(String, Action)>,
+}
+
+impl PrivacyPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replace values with their hex HMAC-SHA256 keyed by the salt. Without the salt a hash
+    // cannot be recomputed from a guessed value, so keep it secret like a key.
+    pub fn hash(mut self, column: &str, salt: &str) -> Self {
+        let salt = salt.to_string();
+        self.rules.push((column.to_string(), Action::Hash { salt }));
+        self
+    }
+
+    // Replace every character but the last `keep_last` ones with '*'
+    pub fn mask(mut self, column: &str, keep_last: usize) -> Self {
+        self.rules
+            .push((column.to_string(), Action::Mask { keep_last }));
+        self
+    }
+
+    pub fn tokenize(mut self, column: &str, tokenizer: Tokenizer) -> Self {
+        self.rules
+            .push((column.to_string(), Action::Tokenize(tokenizer)));
+        self
+    }
+
+    pub fn drop(mut self, column: &str) -> Self {
+        self.rules.push((column.to_string(), Action::Drop));
+        self
+    }
+
+    pub fn apply(&self, table: &Table) -> Result<Table, PrivacyError> {
+        let mut table = table.clone();
+
+        for (column, action) in &self.rules {
+            let index = table
+                .headers
+                .iter()
+                .position(|h| h == column)
+                .ok_or_else(|| PrivacyError::UnknownColumn(UnknownColumn(column.clone())))?;
+
+            if let Action::Drop = action {
+                table.headers.remove(index);
+                for row in table.rows.iter_mut() {
+                    row.remove(index);
+                }
+                continue;
+            }
+
+            for row in table.rows.iter_mut() {
+                row[index] = match action {
+                    Action::Hash { salt } => salted_hash(salt, &row[index]),
+                    Action::Mask { keep_last } => mask(&row[index], *keep_last),
+                    Action::Tokenize(tokenizer) => tokenizer
+                        .tokenize(column, &row[index])
+                        .map_err(PrivacyError::Tokenize)?,
+                    Action::Drop => unreachable!("dropped above"),
+                };
+            }
+        }
+
+        Ok(table)
+    }
+
+    // Rules without their salts and keys, for lineage
+    pub fn describe(&self) -> String {
+        let rules: Vec<_> = self
+            .rules
+            .iter()
+            .map(|(column, action)| match action {
+                Action::Hash { .. } => format!("hash({})", column),
+                Action::Mask { keep_last } => format!("mask({}, {})", column, keep_last),
+                Action::Tokenize(_) => format!("tokenize({})", column),
+                Action::Drop => format!("drop({})", column),
+            })
+            .collect();
+        format!("privacy({})", rules.join(", "))
+    }
+}
+
+fn salted_hash(salt: &str, value: &str) -> String {
+    hex(&hmac_sha256(salt.as_bytes(), value.as_bytes()))
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+fn mask(value: &str, keep_last: usize) -> String {
+    let len = value.chars().count();
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i + keep_last < len { '*' } else { c })
+        .collect()
+}
+
+// Format preserving tokenization: digits stay digits and letters stay letters of the same
+// case, anything else is kept as is, so tokens still pass format checks downstream.
+// Tokens are deterministic for a key and column, and reversible with the same key.
+// The letters and digits of a value are read as one number in mixed radix and encrypted
+// with FF1 (NIST SP 800-38G) under an AES-256 key derived from the key, the column being
+// the tweak. FF1 permutes bit strings, a result outside the values of that format is
+// encrypted again until it falls inside (cycle walking).
+// Formats with fewer than a million values, like a 4 digit id, cannot be tokenized safely
+// and are refused, hash or mask them instead.
+#[derive(Clone)]
+pub struct Tokenizer {
+    key: [u8; 32],
+}
+
+// Keep the key out of logs
+impl std::fmt::Debug for Tokenizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Tokenizer").finish_non_exhaustive()
+    }
+}
+
+// Smallest number of values of a format FF1 may encrypt
+const MIN_DOMAIN: u32 = 1_000_000;
+
+impl Tokenizer {
+    pub fn new(key: &[u8]) -> Self {
+        Tokenizer {
+            key: hmac_sha256(key, b"etl tokenization"),
+        }
+    }
+
+    // Key file content is used as the key, surrounding whitespace ignored
+    pub fn from_key_file(path: &Path) -> std::io::Result<Self> {
+        let content = std::fs::read(path)?;
+        let key = content.trim_ascii();
+        if key.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("empty tokenization key file: {}", path.display()),
+            ));
+        }
+
+        Ok(Tokenizer::new(key))
+    }
+
+    pub fn tokenize(&self, column: &str, value: &str) -> Result<String, TokenizeError> {
+        self.permute(column, value, true)
+    }
+
+    pub fn detokenize(&self, column: &str, token: &str) -> Result<String, TokenizeError> {
+        self.permute(column, token, false)
+    }
+
+    fn permute(&self, column: &str, value: &str, encrypt: bool) -> Result<String, TokenizeError> {
+        let mut chars: Vec<char> = value.chars().collect();
+        let classes: Vec<(usize, u8, u32)> = chars
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &c)| class(c).map(|(base, radix)| (i, base, radix)))
+            .collect();
+        if classes.is_empty() {
+            return Ok(value.to_string());
+        }
+
+        // the value as a number below `domain`, the count of values of its format
+        let mut n = BigUint::ZERO;
+        let mut domain = BigUint::from(1u32);
+        for &(i, base, radix) in &classes {
+            n = n * radix + (chars[i] as u8 - base);
+            domain *= radix;
+        }
+        if domain < BigUint::from(MIN_DOMAIN) {
+            // the value itself is left out of the error, it may end up in logs
+            return Err(TokenizeError(format!(
+                "a value of column {} has too few letters and digits to be tokenized",
+                column
+            )));
+        }
+
+        let bits = (&domain - 1u32).bits();
+        let ff1 = FF1::<Aes256>::new(&self.key, 2).expect("radix 2 is valid");
+        loop {
+            let numerals: Vec<u16> = (0..bits).rev().map(|b| n.bit(b) as u16).collect();
+            let numerals = FlexibleNumeralString::from(numerals);
+            let permuted = if encrypt {
+                ff1.encrypt(column.as_bytes(), &numerals)
+            } else {
+                ff1.decrypt(column.as_bytes(), &numerals)
+            }
+            .map_err(|e| TokenizeError(format!("cannot tokenize column {}: {}", column, e)))?;
+
+            n = Vec::<u16>::from(permuted)
+                .into_iter()
+                .fold(BigUint::ZERO, |n, bit| (n << 1u8) + bit);
+            if n < domain {
+                break;
+            }
+        }
+
+        for &(i, base, radix) in classes.iter().rev() {
+            let digit = u8::try_from(&n % radix).expect("digit is below the radix");
+            chars[i] = (base + digit) as char;
+            n /= radix;
+        }
+        Ok(chars.into_iter().collect())
+    }
+}
+
+// First character and size of the class of a tokenized character
+fn class(c: char) -> Option<(u8, u32)> {
+    match c {
+        '0'..='9' => Some((b'0', 10)),
+        'a'..='z' => Some((b'a', 26)),
+        'A'..='Z' => Some((b'A', 26)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        Table {
+            headers: vec!["id".to_string(), "value".to_string()],
+            rows: vec![
+                vec!["1234567".to_string(), "10".to_string()],
+                vec!["42".to_string(), "20".to_string()],
+            ],
+        }
+    }
+
+    #[test]
+    fn debug_output_hides_salts_and_keys() {
+        let policy = PrivacyPolicy::new()
+            .hash("id", "s3cret-salt")
+            .mask("value", 2)
+            .tokenize("id", Tokenizer::new(b"s3cret-key"));
+
+        let debug = format!("{:?}", policy);
+
+        assert!(!debug.contains("s3cret"), "{}", debug);
+        assert!(debug.contains("Hash { .. }"), "{}", debug);
+        assert!(debug.contains("keep_last: 2"), "{}", debug);
+    }
+
+    #[test]
+    fn hash_is_salted_and_deterministic() {
+        let a = PrivacyPolicy::new()
+            .hash("id", "pepper")
+            .apply(&table())
+            .unwrap();
+        let b = PrivacyPolicy::new()
+            .hash("id", "pepper")
+            .apply(&table())
+            .unwrap();
+        let c = PrivacyPolicy::new()
+            .hash("id", "salt")
+            .apply(&table())
+            .unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a.rows[0][0], c.rows[0][0]);
+        // HMAC-SHA256 with the salt as key
+        assert_eq!(
+            a.rows[0][0],
+            "518e120b3ef4ff197365b07d4e1bcdc9d76a6d0278a3291a4fc0d98468aa6c08"
+        );
+        assert_eq!(a.rows[0][1], "10");
+    }
+
+    #[test]
+    fn mask_keeps_the_last_characters() {
+        let masked = PrivacyPolicy::new().mask("id", 3).apply(&table()).unwrap();
+
+        assert_eq!(masked.rows[0][0], "****567");
+        assert_eq!(masked.rows[1][0], "42");
+    }
+
+    #[test]
+    fn drop_removes_the_column() {
+        let dropped = PrivacyPolicy::new().drop("id").apply(&table()).unwrap();
+
+        assert_eq!(dropped.headers, vec!["value"]);
+        assert_eq!(dropped.rows, vec![vec!["10"], vec!["20"]]);
+    }
+
+    #[test]
+    fn unknown_column_is_an_error() {
+        let result = PrivacyPolicy::new().mask("email", 2).apply(&table());
+
+        assert_eq!(
+            result,
+            Err(PrivacyError::UnknownColumn(UnknownColumn(
+                "email".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn tokens_preserve_format_and_reverse_with_the_key() {
+        let tokenizer = Tokenizer::new(b"secret key");
+        let value = "FR76-3000 6000 0112 3456 7890 189";
+
+        let token = tokenizer.tokenize("iban", value).unwrap();
+
+        assert_ne!(token, value);
+        assert_eq!(token.len(), value.len());
+        for (t, v) in token.chars().zip(value.chars()) {
+            assert_eq!(t.is_ascii_digit(), v.is_ascii_digit());
+            assert_eq!(t.is_ascii_uppercase(), v.is_ascii_uppercase());
+            if !v.is_ascii_alphanumeric() {
+                assert_eq!(t, v);
+            }
+        }
+        assert_eq!(tokenizer.tokenize("iban", value).unwrap(), token);
+        assert_eq!(tokenizer.detokenize("iban", &token).unwrap(), value);
+        assert_ne!(
+            Tokenizer::new(b"other key")
+                .tokenize("iban", value)
+                .unwrap(),
+            token
+        );
+        assert_ne!(tokenizer.tokenize("account", value).unwrap(), token);
+    }
+
+    #[test]
+    fn last_digit_of_a_token_does_not_follow_the_value() {
+        let tokenizer = Tokenizer::new(b"secret key");
+
+        // values sharing a prefix, only the last digit differs
+        let shifts: std::collections::HashSet<_> = (0..10)
+            .map(|d| {
+                let value = format!("400000123{}", d);
+                let token = tokenizer.tokenize("id", &value).unwrap();
+                assert_eq!(tokenizer.detokenize("id", &token).unwrap(), value);
+                let last = |s: &str| s.chars().last().unwrap().to_digit(10).unwrap();
+                (last(&token) + 10 - last(&value)) % 10
+            })
+            .collect();
+
+        assert!(shifts.len() > 1);
+    }
+
+    #[test]
+    fn small_formats_are_refused_without_the_value() {
+        let tokenizer = Tokenizer::new(b"secret key");
+
+        let err = tokenizer.tokenize("id", "12345").unwrap_err();
+        let result = PrivacyPolicy::new()
+            .tokenize("id", tokenizer.clone())
+            .apply(&table());
+
+        assert!(!err.0.contains("12345"));
+        assert!(matches!(result, Err(PrivacyError::Tokenize(_))));
+        assert_eq!(tokenizer.tokenize("id", "123456").unwrap().len(), 6);
+        assert_eq!(tokenizer.tokenize("id", "--").unwrap(), "--");
+    }
+
+    #[test]
+    fn tokenizer_reads_key_file() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let key = dir.path().join("etl.key");
+        std::fs::write(&key, "secret key\n").unwrap();
+        std::fs::write(dir.path().join("empty.key"), "\n").unwrap();
+
+        let tokenizer = Tokenizer::from_key_file(&key).expect("Error reading key");
+
+        assert_eq!(
+            tokenizer.tokenize("id", "12345678"),
+            Tokenizer::new(b"secret key").tokenize("id", "12345678")
+        );
+        assert!(Tokenizer::from_key_file(&dir.path().join("empty.key")).is_err());
+        assert!(Tokenizer::from_key_file(&dir.path().join("missing.key")).is_err());
+    }
+}
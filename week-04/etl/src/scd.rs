@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::load::{Loader, Table};
+use crate::CleanData;
+
+// A versioned row of a slowly changing dimension (type 2) table.
+// `valid_to` is exclusive and empty for the current version of an id.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScdRecord {
+    pub id: u32,
+    pub value: i32,
+    pub valid_from: NaiveDate,
+    pub valid_to: Option<NaiveDate>,
+    pub is_current: bool,
+}
+
+// Merge incoming cleaned data into the stored history.
+// A changed value closes the current version and opens a new one at `load_date`,
+// unchanged values are left alone and ids missing from the batch keep their history.
+// Loads are applied in date order, a load dated before the last one is refused
+// as it would close versions before they start.
+pub fn merge_scd2(
+    mut history: Vec<ScdRecord>,
+    incoming: &[CleanData],
+    load_date: NaiveDate,
+) -> std::io::Result<Vec<ScdRecord>> {
+    let last_load = history
+        .iter()
+        .flat_map(|r| [Some(r.valid_from), r.valid_to])
+        .flatten()
+        .max();
+    if let Some(last_load) = last_load.filter(|&d| load_date < d) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "load date {} is before the last load on {}",
+                load_date, last_load
+            ),
+        ));
+    }
+
+    let mut current: HashMap<u32, usize> = history
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.is_current)
+        .map(|(i, r)| (r.id, i))
+        .collect();
+
+    for item in incoming {
+        match current.get(&item.id) {
+            Some(&i) if history[i].value == item.value => {}
+            Some(&i) if history[i].valid_from == load_date => {
+                history[i].value = item.value;
+            }
+            Some(&i) => {
+                history[i].valid_to = Some(load_date);
+                history[i].is_current = false;
+
+                current.insert(item.id, history.len());
+                history.push(new_version(item, load_date));
+            }
+            None => {
+                current.insert(item.id, history.len());
+                history.push(new_version(item, load_date));
+            }
+        }
+    }
+
+    Ok(history)
+}
+
+fn new_version(item: &CleanData, load_date: NaiveDate) -> ScdRecord {
+    ScdRecord {
+        id: item.id,
+        value: item.value,
+        valid_from: load_date,
+        valid_to: None,
+        is_current: true,
+    }
+}
+
+// Value an id had on a given date, if any version was valid then
+pub fn value_as_of(history: &[ScdRecord], id: u32, date: NaiveDate) -> Option<i32> {
+    history
+        .iter()
+        .find(|r| r.id == id && r.valid_from <= date && r.valid_to.is_none_or(|to| date < to))
+        .map(|r| r.value)
+}
+
+const SCD2_HEADERS: [&str; 5] = ["id", "value", "valid_from", "valid_to", "is_current"];
+
+// History stored in an SCD2 file, empty when the file does not exist yet.
+// A file with other columns, like the output of write_to_csv, is refused
+// rather than overwritten.
+pub fn read_scd2_csv(filename: &str) -> std::io::Result<Vec<ScdRecord>> {
+    if !Path::new(filename).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_path(filename)?;
+
+    let headers = rdr.headers()?;
+    if !headers.is_empty() && !headers.iter().eq(SCD2_HEADERS) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} is not an SCD2 history, expected the columns {}",
+                filename,
+                SCD2_HEADERS.join(";")
+            ),
+        ));
+    }
+
+    let mut history = Vec::new();
+    for result in rdr.deserialize() {
+        history.push(result?);
+    }
+
+    Ok(history)
+}
+
+pub fn write_scd2_csv(history: &[ScdRecord], filename: &str) -> std::io::Result<()> {
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_path(filename)?;
+
+    for item in history {
+        wtr.serialize(item)?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+// SCD2 load mode: unlike write_to_csv, keeps every previous value of an id in the file
+pub fn load_scd2_csv(
+    cleaned: &[CleanData],
+    filename: &str,
+    load_date: NaiveDate,
+) -> std::io::Result<()> {
+    let history = read_scd2_csv(filename)?;
+    let history = merge_scd2(history, cleaned, load_date)?;
+    write_scd2_csv(&history, filename)
+}
+
+// Loader running load_scd2_csv, every load of a pipeline is dated `load_date`
+pub struct ScdLoader {
+    path: PathBuf,
+    load_date: NaiveDate,
+}
+
+impl ScdLoader {
+    pub fn new(path: impl Into<PathBuf>, load_date: NaiveDate) -> Self {
+        ScdLoader {
+            path: path.into(),
+            load_date,
+        }
+    }
+
+    fn filename(&self) -> std::io::Result<&str> {
+        self.path.to_str().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not valid UTF-8", self.path.display()),
+            )
+        })
+    }
+}
+
+impl Loader for ScdLoader {
+    fn load(&mut self, table: &Table) -> std::io::Result<()> {
+        self.load_cleaned(&table.cleaned()?)
+    }
+
+    fn load_cleaned(&mut self, cleaned: &[CleanData]) -> std::io::Result<()> {
+        load_scd2_csv(cleaned, self.filename()?, self.load_date)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Clamp, Pipeline, RawData};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn first_load_inserts_current_versions() {
+        let cleaned = vec![
+            CleanData { id: 1, value: 10 },
+            CleanData { id: 2, value: 20 },
+        ];
+
+        let history = merge_scd2(Vec::new(), &cleaned, date("2024-01-01")).expect("Error merging");
+
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|r| r.is_current && r.valid_to.is_none()));
+        assert_eq!(history[0].valid_from, date("2024-01-01"));
+    }
+
+    #[test]
+    fn changed_value_closes_previous_version() {
+        let first = vec![
+            CleanData { id: 1, value: 10 },
+            CleanData { id: 2, value: 20 },
+        ];
+        let second = vec![
+            CleanData { id: 1, value: 15 },
+            CleanData { id: 2, value: 20 },
+        ];
+
+        let history = merge_scd2(Vec::new(), &first, date("2024-01-01")).expect("Error merging");
+        let history = merge_scd2(history, &second, date("2024-02-01")).expect("Error merging");
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].valid_to, Some(date("2024-02-01")));
+        assert!(!history[0].is_current);
+        assert!(history[1].is_current);
+        assert_eq!(history[2].id, 1);
+        assert_eq!(history[2].value, 15);
+        assert!(history[2].is_current);
+    }
+
+    #[test]
+    fn value_as_of_returns_version_valid_on_date() {
+        let history = merge_scd2(
+            Vec::new(),
+            &[CleanData { id: 1, value: 10 }],
+            date("2024-01-01"),
+        )
+        .expect("Error merging");
+        let history = merge_scd2(
+            history,
+            &[CleanData { id: 1, value: 15 }],
+            date("2024-02-01"),
+        )
+        .expect("Error merging");
+
+        assert_eq!(value_as_of(&history, 1, date("2023-12-31")), None);
+        assert_eq!(value_as_of(&history, 1, date("2024-01-15")), Some(10));
+        assert_eq!(value_as_of(&history, 1, date("2024-02-01")), Some(15));
+        assert_eq!(value_as_of(&history, 2, date("2024-02-01")), None);
+    }
+
+    #[test]
+    fn load_dated_before_last_load_is_refused() {
+        let history = merge_scd2(
+            Vec::new(),
+            &[CleanData { id: 1, value: 10 }],
+            date("2024-02-01"),
+        )
+        .expect("Error merging");
+
+        let err = merge_scd2(
+            history,
+            &[CleanData { id: 1, value: 15 }],
+            date("2024-01-01"),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn load_scd2_csv_keeps_history_across_runs() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let file_name = dir.path().join("history.csv");
+        let file_name = file_name.to_str().unwrap();
+
+        load_scd2_csv(
+            &[CleanData { id: 1, value: 10 }],
+            file_name,
+            date("2024-01-01"),
+        )
+        .expect("Error loading SCD2");
+        load_scd2_csv(
+            &[CleanData { id: 1, value: 15 }],
+            file_name,
+            date("2024-02-01"),
+        )
+        .expect("Error loading SCD2");
+
+        let reader = std::fs::read_to_string(file_name).expect("Error reading file");
+        assert_eq!(
+            reader,
+            "id;value;valid_from;valid_to;is_current\n\
+             1;10;2024-01-01;2024-02-01;false\n\
+             1;15;2024-02-01;;true\n"
+        );
+    }
+
+    #[test]
+    fn file_without_history_columns_is_refused() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let file_name = dir.path().join("cleaned.csv");
+        let file_name = file_name.to_str().unwrap();
+        std::fs::write(file_name, "id;value\n1;10\n").expect("Error writing file");
+
+        let err = load_scd2_csv(
+            &[CleanData { id: 1, value: 15 }],
+            file_name,
+            date("2024-02-01"),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let content = std::fs::read_to_string(file_name).expect("Error reading file");
+        assert_eq!(content, "id;value\n1;10\n");
+    }
+
+    #[test]
+    fn pipeline_sink_keeps_history() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("history.csv");
+
+        for (value, load_date) in [(10, "2024-01-01"), (15, "2024-02-01")] {
+            Pipeline::new(vec![RawData { id: 1, value }])
+                .transform(Clamp::default())
+                .sink(ScdLoader::new(&path, date(load_date)))
+                .run()
+                .expect("Error running pipeline");
+        }
+
+        let history = read_scd2_csv(path.to_str().unwrap()).expect("Error reading history");
+        assert_eq!(history.len(), 2);
+        assert_eq!(value_as_of(&history, 1, date("2024-01-15")), Some(10));
+        assert_eq!(value_as_of(&history, 1, date("2024-02-15")), Some(15));
+    }
+}
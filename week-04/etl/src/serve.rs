@@ -0,0 +1,229 @@
+// Local HTTP ingestion: POST a batch of raw data to /batch as JSON
+// (`[{"id": 1, "value": 10}]`) or CSV (`id;value` header, ';' or ',' delimited).
+// Each batch goes through the same cleaning as extract_transform_load, is appended
+// to the sink file, upserted into the key-value store when one is set, and answered
+// with its summary.
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{Clamp, CsvLoader, ErrorPolicy, KvLoader, Pipeline, RawData, Summary};
+
+#[derive(Debug, PartialEq)]
+pub enum BatchError {
+    UnsupportedContentType(String),
+    Malformed(String),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchError::UnsupportedContentType(t) => write!(f, "unsupported content type: {}", t),
+            BatchError::Malformed(err) => write!(f, "malformed batch: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+pub fn parse_batch(body: &str, content_type: &str) -> Result<Vec<RawData>, BatchError> {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+
+    match mime {
+        "application/json" => {
+            serde_json::from_str(body).map_err(|e| BatchError::Malformed(e.to_string()))
+        }
+        "text/csv" => {
+            let header = body.lines().next().unwrap_or("");
+            let delimiter = if header.contains(';') { b';' } else { b',' };
+
+            csv::ReaderBuilder::new()
+                .has_headers(true)
+                .delimiter(delimiter)
+                .trim(csv::Trim::All)
+                .from_reader(body.as_bytes())
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .map_err(|e| BatchError::Malformed(e.to_string()))
+        }
+        _ => Err(BatchError::UnsupportedContentType(mime.to_string())),
+    }
+}
+
+#[derive(Serialize)]
+struct BatchResponse {
+    #[serde(flatten)]
+    summary: Summary,
+    rejected: usize,
+}
+
+pub struct BatchServer {
+    server: Server,
+    sink: PathBuf,
+    kv: Option<KvLoader>,
+}
+
+impl BatchServer {
+    pub fn bind(addr: &str, sink: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let server = Server::http(addr).map_err(std::io::Error::other)?;
+
+        Ok(BatchServer {
+            server,
+            sink: sink.into(),
+            kv: None,
+        })
+    }
+
+    // Also upsert every batch into a key-value store
+    pub fn kv(mut self, store: KvLoader) -> Self {
+        self.kv = Some(store);
+        self
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    // Handle requests one at a time, so batches are appended to the sink in order
+    pub fn run(&self) -> std::io::Result<()> {
+        for request in self.server.incoming_requests() {
+            self.handle(request)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&self, mut request: Request) -> std::io::Result<()> {
+        if request.url() != "/batch" {
+            return request.respond(json_response(404, error_body("not found")));
+        }
+        if request.method() != &Method::Post {
+            return request.respond(json_response(405, error_body("use POST")));
+        }
+
+        let content_type = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Content-Type"))
+            .map(|h| h.value.as_str().to_string())
+            .unwrap_or_default();
+        let mut body = String::new();
+        if let Err(err) = request.as_reader().read_to_string(&mut body) {
+            return request.respond(json_response(400, error_body(&err.to_string())));
+        }
+
+        let (status, body) = match parse_batch(&body, &content_type) {
+            Err(err @ BatchError::UnsupportedContentType(_)) => (415, error_body(&err.to_string())),
+            Err(err) => (400, error_body(&err.to_string())),
+            Ok(raw) => {
+                let mut pipeline = Pipeline::new(raw)
+                    .transform(Clamp::default())
+                    .sink(CsvLoader::new(&self.sink).append(true))
+                    .error_policy(ErrorPolicy::Reject)
+                    .lineage(true);
+                if let Some(kv) = &self.kv {
+                    pipeline = pipeline.sink(kv.clone());
+                }
+                let result = pipeline.run();
+                match result {
+                    Ok(report) => (
+                        200,
+                        serde_json::to_string(&BatchResponse {
+                            summary: report.summary,
+                            rejected: report.rejects.len(),
+                        })
+                        .expect("summary is always serializable"),
+                    ),
+                    Err(err) => (500, error_body(&err.to_string())),
+                }
+            }
+        };
+
+        request.respond(json_response(status, body))
+    }
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn json_response(status: u16, body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("static header is valid");
+
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use super::*;
+
+    fn post(addr: SocketAddr, content_type: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).expect("Error connecting");
+        write!(
+            stream,
+            "POST /batch HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        )
+        .expect("Error sending request");
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .expect("Error reading response");
+        response
+    }
+
+    #[test]
+    fn parse_json_and_csv_batches() {
+        let json = parse_batch(r#"[{"id": 1, "value": -5}]"#, "application/json").unwrap();
+        let csv = parse_batch("id,value\n1,-5\n2,120\n", "text/csv; charset=utf-8").unwrap();
+        let semicolon = parse_batch("id;value\n1;-5\n", "text/csv").unwrap();
+
+        assert_eq!((json[0].id, json[0].value), (1, -5));
+        assert_eq!(csv.len(), 2);
+        assert_eq!((csv[1].id, csv[1].value), (2, 120));
+        assert_eq!(semicolon.len(), 1);
+    }
+
+    #[test]
+    fn parse_rejects_bad_batches() {
+        assert!(matches!(
+            parse_batch("<xml/>", "application/xml"),
+            Err(BatchError::UnsupportedContentType(t)) if t == "application/xml"
+        ));
+        assert!(matches!(
+            parse_batch("id;value\n1;abc\n", "text/csv"),
+            Err(BatchError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn posted_batches_are_cleaned_appended_and_summarized() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let sink = dir.path().join("cleaned.csv");
+        let server = BatchServer::bind("127.0.0.1:0", &sink).expect("Error binding");
+        let addr = server.local_addr().expect("Error getting address");
+        std::thread::spawn(move || server.run());
+
+        let first = post(addr, "application/json", r#"[{"id":1,"value":-5}]"#);
+        let second = post(addr, "text/csv", "id;value\n2;120\n3;40\n");
+        let bad = post(addr, "text/plain", "hello");
+
+        assert!(first.starts_with("HTTP/1.1 200"));
+        assert!(second.contains(r#""count":2,"total":140"#));
+        assert!(bad.starts_with("HTTP/1.1 415"));
+        let written = std::fs::read_to_string(&sink).expect("Error reading sink");
+        assert_eq!(written, "id;value\n1;0\n2;100\n3;40\n");
+    }
+}
@@ -0,0 +1,309 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::group::UnknownColumn;
+use crate::CleanData;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl SortKey {
+    pub fn asc(column: &str) -> Self {
+        SortKey {
+            column: column.to_string(),
+            descending: false,
+        }
+    }
+
+    pub fn desc(column: &str) -> Self {
+        SortKey {
+            column: column.to_string(),
+            descending: true,
+        }
+    }
+}
+
+// Stable sort by one or more columns. Data larger than the memory budget is cut into
+// sorted runs spilled to temporary files, then merged into the output file.
+// At most `merge_width` runs are open at once, more runs are merged in several passes.
+#[derive(Debug, Clone)]
+pub struct ExternalSort {
+    keys: Vec<SortKey>,
+    run_len: usize,
+    merge_width: usize,
+}
+
+impl ExternalSort {
+    pub fn new(keys: Vec<SortKey>) -> Result<Self, UnknownColumn> {
+        if let Some(key) = keys
+            .iter()
+            .find(|k| !matches!(k.column.as_str(), "id" | "value"))
+        {
+            return Err(UnknownColumn(key.column.clone()));
+        }
+
+        Ok(ExternalSort {
+            keys,
+            run_len: usize::MAX,
+            merge_width: 64,
+        })
+    }
+
+    // Approximate number of bytes of records held in memory at once
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.run_len = (bytes / std::mem::size_of::<CleanData>()).max(1);
+        self
+    }
+
+    // Number of run files opened at once by a merge, 64 by default
+    pub fn merge_width(mut self, runs: usize) -> Self {
+        self.merge_width = runs.max(2);
+        self
+    }
+
+    pub(crate) fn has_memory_budget(&self) -> bool {
+        self.run_len != usize::MAX
+    }
+
+    fn key(&self, record: &CleanData) -> Vec<i64> {
+        self.keys
+            .iter()
+            .map(|k| {
+                let v = match k.column.as_str() {
+                    "id" => record.id as i64,
+                    _ => record.value as i64,
+                };
+                if k.descending {
+                    -v
+                } else {
+                    v
+                }
+            })
+            .collect()
+    }
+
+    pub fn sort(&self, records: &mut [CleanData]) {
+        self.sort_by_record(records, |r| r);
+    }
+
+    // Sort items carrying a record, e.g. records paired with their provenance
+    pub fn sort_by_record<T>(&self, items: &mut [T], record: impl Fn(&T) -> &CleanData) {
+        items.sort_by_cached_key(|item| self.key(record(item)));
+    }
+
+    pub fn describe(&self) -> String {
+        let keys: Vec<_> = self
+            .keys
+            .iter()
+            .map(|k| format!("{} {}", k.column, if k.descending { "desc" } else { "asc" }))
+            .collect();
+        format!("sort({})", keys.join(", "))
+    }
+
+    // Sort a `id;value` file into another, whatever its size
+    pub fn sort_csv(&self, input: &Path, output: &Path) -> std::io::Result<()> {
+        let spill = tempfile::tempdir()?;
+        let mut runs = Vec::new();
+        let mut buffer = Vec::new();
+
+        let mut rdr = csv_reader(input)?;
+        for result in rdr.deserialize() {
+            buffer.push(result?);
+            if buffer.len() >= self.run_len {
+                runs.push(self.spill(&mut buffer, spill.path(), runs.len())?);
+            }
+        }
+        if !buffer.is_empty() || runs.is_empty() {
+            runs.push(self.spill(&mut buffer, spill.path(), runs.len())?);
+        }
+
+        // consecutive runs are merged together, so earlier input still comes first on ties
+        let mut pass = 0;
+        while runs.len() > self.merge_width {
+            runs = runs
+                .chunks(self.merge_width)
+                .enumerate()
+                .map(|(i, group)| {
+                    let merged = spill.path().join(format!("merge-{pass}-{i}.csv"));
+                    self.merge(group, &merged)?;
+                    for run in group {
+                        fs::remove_file(run)?;
+                    }
+                    Ok(merged)
+                })
+                .collect::<std::io::Result<_>>()?;
+            pass += 1;
+        }
+
+        self.merge(&runs, output)
+    }
+
+    fn spill(
+        &self,
+        buffer: &mut Vec<CleanData>,
+        dir: &Path,
+        index: usize,
+    ) -> std::io::Result<PathBuf> {
+        self.sort(buffer);
+
+        let path = dir.join(format!("run-{index}.csv"));
+        crate::write_to_csv(buffer, path.to_str().expect("temp path is not UTF-8"))?;
+        buffer.clear();
+        Ok(path)
+    }
+
+    fn merge(&self, runs: &[PathBuf], output: &Path) -> std::io::Result<()> {
+        let mut readers = runs
+            .iter()
+            .map(|run| csv_reader(run).map(|r| r.into_deserialize::<CleanData>()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let mut heads: Vec<Option<CleanData>> = Vec::with_capacity(readers.len());
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            let head = reader.next().transpose()?;
+            if let Some(record) = &head {
+                heap.push(Reverse((self.key(record), i)));
+            }
+            heads.push(head);
+        }
+
+        // header written by hand so an empty input still gets one
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .delimiter(b';')
+            .from_path(output)?;
+        wtr.write_record(["id", "value"])?;
+
+        // Ties are broken by run index, runs hold earlier input first so the merge stays stable
+        while let Some(Reverse((_, i))) = heap.pop() {
+            let record = heads[i].take().expect("heap entry without a head");
+            wtr.serialize(&record)?;
+
+            heads[i] = readers[i].next().transpose()?;
+            if let Some(next) = &heads[i] {
+                heap.push(Reverse((self.key(next), i)));
+            }
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+fn csv_reader(path: &Path) -> std::io::Result<csv::Reader<std::fs::File>> {
+    Ok(csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_path(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(records: &[CleanData]) -> Vec<u32> {
+        records.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn sort_by_several_columns() {
+        let mut records = vec![
+            CleanData { id: 1, value: 20 },
+            CleanData { id: 2, value: 10 },
+            CleanData { id: 3, value: 20 },
+            CleanData { id: 4, value: 30 },
+        ];
+
+        let sort = ExternalSort::new(vec![SortKey::desc("value"), SortKey::asc("id")]).unwrap();
+        sort.sort(&mut records);
+
+        assert_eq!(ids(&records), vec![4, 1, 3, 2]);
+    }
+
+    #[test]
+    fn unknown_sort_column_is_an_error() {
+        let result = ExternalSort::new(vec![SortKey::asc("country")]);
+
+        assert_eq!(result.err(), Some(UnknownColumn("country".to_string())));
+    }
+
+    #[test]
+    fn sort_csv_spills_and_merges_runs() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let input = dir.path().join("input.csv");
+        let output = dir.path().join("output.csv");
+        let records: Vec<_> = (0..100)
+            .map(|i| CleanData {
+                id: (i * 37) % 100,
+                value: (i % 7) as i32,
+            })
+            .collect();
+        crate::write_to_csv(&records, input.to_str().unwrap()).expect("Error writing input");
+
+        // room for 8 records, so 13 runs on disk merged 3 at a time: 5 runs, 2, then 1
+        let sort = ExternalSort::new(vec![SortKey::asc("value"), SortKey::desc("id")])
+            .unwrap()
+            .memory_budget(8 * std::mem::size_of::<CleanData>())
+            .merge_width(3);
+        sort.sort_csv(&input, &output).expect("Error sorting");
+
+        let mut expected = records;
+        sort.sort(&mut expected);
+        let sorted: Vec<CleanData> = csv_reader(&output)
+            .unwrap()
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .expect("Error reading output");
+        assert_eq!(sorted.len(), 100);
+        assert_eq!(ids(&sorted), ids(&expected));
+    }
+
+    #[test]
+    fn merge_passes_keep_ties_in_input_order() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let input = dir.path().join("input.csv");
+        let output = dir.path().join("output.csv");
+        let records: Vec<_> = (0..50)
+            .map(|id| CleanData {
+                id,
+                value: (id % 3) as i32,
+            })
+            .collect();
+        crate::write_to_csv(&records, input.to_str().unwrap()).expect("Error writing input");
+
+        let sort = ExternalSort::new(vec![SortKey::asc("value")])
+            .unwrap()
+            .memory_budget(2 * std::mem::size_of::<CleanData>())
+            .merge_width(2);
+        sort.sort_csv(&input, &output).expect("Error sorting");
+
+        let sorted: Vec<CleanData> = csv_reader(&output)
+            .unwrap()
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .expect("Error reading output");
+        let mut expected = records;
+        expected.sort_by_key(|r| r.value);
+        assert_eq!(ids(&sorted), ids(&expected));
+    }
+
+    #[test]
+    fn sort_csv_of_empty_file_writes_header() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let input = dir.path().join("input.csv");
+        let output = dir.path().join("output.csv");
+        std::fs::write(&input, "id;value\n").expect("Error writing input");
+
+        let sort = ExternalSort::new(vec![SortKey::asc("id")]).unwrap();
+        sort.sort_csv(&input, &output).expect("Error sorting");
+
+        let written = std::fs::read_to_string(&output).expect("Error reading output");
+        assert_eq!(written, "id;value\n");
+    }
+}
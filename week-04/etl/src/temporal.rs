@@ -0,0 +1,238 @@
+// Timestamp parsing for time-stamped feeds: raw events carry their timestamp as text,
+// parsed with the configured formats, converted to UTC and optionally truncated.
+// Events whose timestamp cannot be parsed are rejected instead of failing the run.
+use chrono::{DateTime, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::pipeline::Reject;
+use crate::TimedData;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RawEvent {
+    pub id: u32,
+    pub value: i32,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Truncate {
+    Hour,
+    Day,
+}
+
+// Formats use chrono's strftime syntax and are tried in order. A format with an offset
+// (%z, %:z) is converted from that offset, a format without one is read in the parser
+// timezone (UTC unless set), a date only format means midnight. "rfc3339" is also accepted.
+#[derive(Debug, Clone)]
+pub struct TimestampParser {
+    formats: Vec<String>,
+    timezone: Tz,
+    truncate: Option<Truncate>,
+}
+
+impl TimestampParser {
+    pub fn new(formats: &[&str]) -> Self {
+        TimestampParser {
+            formats: formats.iter().map(|f| f.to_string()).collect(),
+            timezone: Tz::UTC,
+            truncate: None,
+        }
+    }
+
+    // Timezone of timestamps without an offset, e.g. "Europe/Paris"
+    pub fn timezone(mut self, name: &str) -> Result<Self, String> {
+        self.timezone = name
+            .parse()
+            .map_err(|_| format!("unknown timezone: {}", name))?;
+        Ok(self)
+    }
+
+    pub fn truncate(mut self, truncate: Truncate) -> Self {
+        self.truncate = Some(truncate);
+        self
+    }
+
+    // Parse to a UTC date time
+    pub fn parse(&self, text: &str) -> Result<NaiveDateTime, String> {
+        let text = text.trim();
+        let utc = self
+            .formats
+            .iter()
+            .find_map(|format| self.parse_with(text, format))
+            .ok_or_else(|| format!("unparseable timestamp: {:?}", text))??;
+
+        Ok(match self.truncate {
+            None => utc,
+            Some(Truncate::Hour) => utc
+                .date()
+                .and_hms_opt(utc.hour(), 0, 0)
+                .expect("hour of a valid time"),
+            Some(Truncate::Day) => utc.date().and_hms_opt(0, 0, 0).expect("midnight"),
+        })
+    }
+
+    // None when the format does not match, Err when it matches a local time that does
+    // not exist in the timezone (skipped by a daylight saving change)
+    fn parse_with(&self, text: &str, format: &str) -> Option<Result<NaiveDateTime, String>> {
+        if format == "rfc3339" {
+            return DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|dt| Ok(dt.naive_utc()));
+        }
+        if let Ok(dt) = DateTime::parse_from_str(text, format) {
+            return Some(Ok(dt.naive_utc()));
+        }
+
+        let local = NaiveDateTime::parse_from_str(text, format)
+            .or_else(|_| {
+                NaiveDate::parse_from_str(text, format)
+                    .map(|d| d.and_hms_opt(0, 0, 0).expect("midnight"))
+            })
+            .ok()?;
+
+        // an ambiguous time, when clocks go back, is read as the first of the two
+        Some(match self.timezone.from_local_datetime(&local) {
+            LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => Ok(dt.naive_utc()),
+            LocalResult::None => Err(format!(
+                "timestamp {:?} does not exist in {}",
+                text, self.timezone
+            )),
+        })
+    }
+
+    // Timed records ready for aggregate_windows, and the events rejected on their timestamp
+    pub fn parse_events(&self, events: Vec<RawEvent>) -> (Vec<TimedData>, Vec<Reject>) {
+        let mut timed = Vec::with_capacity(events.len());
+        let mut rejects = Vec::new();
+
+        for event in events {
+            match self.parse(&event.timestamp) {
+                Ok(timestamp) => timed.push(TimedData {
+                    id: event.id,
+                    value: event.value,
+                    timestamp,
+                }),
+                Err(reason) => rejects.push(Reject {
+                    id: event.id,
+                    value: event.value,
+                    reason,
+                }),
+            }
+        }
+
+        (timed, rejects)
+    }
+}
+
+// Reads `id;value;timestamp` rows with a header
+pub fn read_events_csv(filename: &str) -> std::io::Result<Vec<RawEvent>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_path(filename)?;
+
+    let mut events = Vec::new();
+    for result in rdr.deserialize() {
+        events.push(result?);
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn formats_are_tried_in_order() {
+        let parser = TimestampParser::new(&["%d/%m/%Y %H:%M", "%Y-%m-%d", "rfc3339"]);
+
+        assert_eq!(
+            parser.parse("31/01/2024 10:30"),
+            Ok(utc("2024-01-31 10:30:00"))
+        );
+        assert_eq!(parser.parse("2024-01-31"), Ok(utc("2024-01-31 00:00:00")));
+        assert_eq!(
+            parser.parse("2024-01-31T10:30:00+02:00"),
+            Ok(utc("2024-01-31 08:30:00"))
+        );
+        assert!(parser.parse("yesterday").is_err());
+    }
+
+    #[test]
+    fn local_times_are_converted_to_utc() {
+        let parser = TimestampParser::new(&["%Y-%m-%d %H:%M"])
+            .timezone("Europe/Paris")
+            .unwrap();
+
+        // winter UTC+1, summer UTC+2
+        assert_eq!(
+            parser.parse("2024-01-15 12:00"),
+            Ok(utc("2024-01-15 11:00:00"))
+        );
+        assert_eq!(
+            parser.parse("2024-07-15 12:00"),
+            Ok(utc("2024-07-15 10:00:00"))
+        );
+        // skipped when clocks went forward
+        assert!(parser.parse("2024-03-31 02:30").is_err());
+        assert!(TimestampParser::new(&[]).timezone("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn offsets_in_the_text_win_over_the_timezone() {
+        let parser = TimestampParser::new(&["%Y-%m-%d %H:%M %z"])
+            .timezone("Asia/Tokyo")
+            .unwrap();
+
+        assert_eq!(
+            parser.parse("2024-01-15 12:00 -0500"),
+            Ok(utc("2024-01-15 17:00:00"))
+        );
+    }
+
+    #[test]
+    fn truncate_to_hour_and_day() {
+        let hour = TimestampParser::new(&["rfc3339"]).truncate(Truncate::Hour);
+        let day = TimestampParser::new(&["rfc3339"]).truncate(Truncate::Day);
+
+        assert_eq!(
+            hour.parse("2024-01-15T12:34:56Z"),
+            Ok(utc("2024-01-15 12:00:00"))
+        );
+        assert_eq!(
+            day.parse("2024-01-15T23:34:56-02:00"),
+            Ok(utc("2024-01-16 00:00:00"))
+        );
+    }
+
+    #[test]
+    fn unparseable_timestamps_are_rejected() {
+        let parser = TimestampParser::new(&["%Y-%m-%d %H:%M:%S"]);
+        let events = vec![
+            RawEvent {
+                id: 1,
+                value: 10,
+                timestamp: "2024-01-15 12:00:00".to_string(),
+            },
+            RawEvent {
+                id: 2,
+                value: 20,
+                timestamp: "15/01/2024".to_string(),
+            },
+        ];
+
+        let (timed, rejects) = parser.parse_events(events);
+
+        assert_eq!(timed.len(), 1);
+        assert_eq!(timed[0].timestamp, utc("2024-01-15 12:00:00"));
+        assert_eq!(rejects.len(), 1);
+        assert_eq!(rejects[0].id, 2);
+        assert_eq!(rejects[0].reason, "unparseable timestamp: \"15/01/2024\"");
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::expr::Rule;
+use crate::CleanData;
+
+// A cleaning step applied to each record in order.
+// Ok(None) drops the record, Err rejects it with a reason.
+pub trait Transform {
+    fn apply(&self, record: CleanData) -> Result<Option<CleanData>, String>;
+
+    // Configuration of the transform, hashed into the lineage
+    fn describe(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+}
+
+// Bring values back into [min, max], the rule used by extract_transform_load
+#[derive(Debug, Clone, Copy)]
+pub struct Clamp {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Default for Clamp {
+    fn default() -> Self {
+        Clamp { min: 0, max: 100 }
+    }
+}
+
+impl Clamp {
+    pub fn value(&self, value: i32) -> i32 {
+        value.clamp(self.min, self.max)
+    }
+}
+
+impl Transform for Clamp {
+    fn apply(&self, record: CleanData) -> Result<Option<CleanData>, String> {
+        Ok(Some(CleanData {
+            id: record.id,
+            value: self.value(record.value),
+        }))
+    }
+
+    fn describe(&self) -> String {
+        format!("clamp({}, {})", self.min, self.max)
+    }
+}
+
+// Any closure can be used as a transform
+impl<F> Transform for F
+where
+    F: Fn(CleanData) -> Result<Option<CleanData>, String>,
+{
+    fn apply(&self, record: CleanData) -> Result<Option<CleanData>, String> {
+        self(record)
+    }
+}
+
+// Transforms configured at runtime, one per line and applied in order.
+// Empty lines and lines starting with # are ignored.
+//
+//     clamp 0 100
+//     rule value = if value < 0 then null else value
+//
+// Every line is parsed and rules are type checked before a transform is returned.
+pub fn parse_transforms(config: &str) -> Result<Vec<Box<dyn Transform>>, String> {
+    let mut transforms: Vec<Box<dyn Transform>> = Vec::new();
+
+    for (n, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at_line = |e: String| format!("line {}: {}", n + 1, e);
+        let (kind, args) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "clamp" => {
+                let bounds = args
+                    .split_whitespace()
+                    .map(|b| b.parse::<i32>())
+                    .collect::<Result<Vec<_>, _>>();
+                match bounds.as_deref() {
+                    Ok(&[min, max]) if min <= max => transforms.push(Box::new(Clamp { min, max })),
+                    _ => return Err(at_line(format!("expected clamp MIN MAX, got {:?}", line))),
+                }
+            }
+            "rule" => {
+                let rule = Rule::parse(args).map_err(|e| at_line(e.to_string()))?;
+                transforms.push(Box::new(rule));
+            }
+            _ => {
+                return Err(at_line(format!(
+                    "unknown transform {:?}, expected clamp or rule",
+                    kind
+                )))
+            }
+        }
+    }
+
+    Ok(transforms)
+}
+
+// parse_transforms on a config file, naming the file in errors
+pub fn read_transforms(path: impl AsRef<Path>) -> std::io::Result<Vec<Box<dyn Transform>>> {
+    let path = path.as_ref();
+    let config = std::fs::read_to_string(path)?;
+    parse_transforms(&config)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_all(transforms: &[Box<dyn Transform>], value: i32) -> Option<i32> {
+        let mut record = CleanData { id: 1, value };
+        for transform in transforms {
+            record = transform.apply(record).expect("Error applying transform")?;
+        }
+        Some(record.value)
+    }
+
+    #[test]
+    fn config_lines_become_transforms_in_order() {
+        let config = "# drop negative values, then cap\n\
+                      rule value = if value < 0 then null else value * 2\n\
+                      \n\
+                      clamp 0 100\n";
+
+        let transforms = parse_transforms(config).expect("Error parsing config");
+
+        assert_eq!(transforms.len(), 2);
+        assert_eq!(apply_all(&transforms, -3), None);
+        assert_eq!(apply_all(&transforms, 20), Some(40));
+        assert_eq!(apply_all(&transforms, 70), Some(100));
+    }
+
+    #[test]
+    fn bad_lines_are_reported_with_their_number() {
+        let err = |config: &str| parse_transforms(config).err().expect("expected an error");
+
+        assert!(err("clamp 0 100\nrule value = value +").starts_with("line 2: "));
+        assert!(err("clamp 100 0").starts_with("line 1: "));
+        assert!(err("clamp 0").starts_with("line 1: "));
+        assert!(err("rule value = true").starts_with("line 1: "));
+        assert!(err("scale 2").contains("unknown transform"));
+    }
+
+    #[test]
+    fn read_transforms_names_the_file() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("transforms.txt");
+        std::fs::write(&path, "clamp a b\n").expect("Error writing config");
+
+        let err = read_transforms(&path).err().expect("expected an error");
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("transforms.txt: line 1"));
+    }
+}
@@ -0,0 +1,363 @@
+// Inbox directory watch: every file dropped in the inbox is run through the pipeline,
+// appended to the sink and moved to `processed/` or `failed/`, or to `skipped/` when it
+// was already processed.
+// The directory is polled rather than subscribed to, as file events are not reliable on
+// shared drives. A file is picked up once its size and modification time did not change
+// between two polls, so a file still being copied is left alone.
+// Handled files are recorded in `.ledger.csv` in the inbox with the SHA-256 of their
+// content, hidden files are ignored. A file is skipped only when a file with the same name
+// and content was processed before, so a feed reusing its name and a fixed failed file are
+// processed again. Files are never overwritten in `processed/`, `failed/` or `skipped/`,
+// a name already taken there gets a number, e.g. `daily.1.csv`.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::lineage::file_sha256;
+use crate::{Clamp, CsvLoader, CsvSource, ErrorPolicy, KvLoader, Pipeline};
+
+const LEDGER: &str = ".ledger.csv";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub file: String,
+    pub sha256: String,
+    pub status: String,
+    // where the file was moved, relative to the inbox
+    pub moved_to: String,
+    pub rows: usize,
+    pub rejected: usize,
+    pub message: String,
+    pub handled_at: NaiveDateTime,
+}
+
+pub struct Watcher {
+    inbox: PathBuf,
+    sink: PathBuf,
+    kv: Option<KvLoader>,
+    interval: Duration,
+    pending: HashMap<PathBuf, (u64, SystemTime)>,
+    ledger: Vec<LedgerEntry>,
+}
+
+impl Watcher {
+    pub fn new(inbox: impl Into<PathBuf>, sink: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let inbox = inbox.into();
+        fs::create_dir_all(inbox.join("processed"))?;
+        fs::create_dir_all(inbox.join("failed"))?;
+        fs::create_dir_all(inbox.join("skipped"))?;
+
+        let ledger_path = inbox.join(LEDGER);
+        let mut ledger = Vec::new();
+        if ledger_path.exists() {
+            let mut rdr = csv::ReaderBuilder::new()
+                .delimiter(b';')
+                .from_path(&ledger_path)?;
+            for result in rdr.deserialize() {
+                ledger.push(result?);
+            }
+        }
+
+        Ok(Watcher {
+            inbox,
+            sink: sink.into(),
+            kv: None,
+            interval: Duration::from_secs(5),
+            pending: HashMap::new(),
+            ledger,
+        })
+    }
+
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    // Also upsert every processed file into a key-value store
+    pub fn kv(mut self, store: KvLoader) -> Self {
+        self.kv = Some(store);
+        self
+    }
+
+    pub fn ledger(&self) -> &[LedgerEntry] {
+        &self.ledger
+    }
+
+    pub fn run(&mut self) -> std::io::Result<()> {
+        loop {
+            for entry in self.poll()? {
+                println!("{} {}: {}", entry.status, entry.file, entry.message);
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+
+    // Scan the inbox once and handle the files that stopped changing since the last scan
+    pub fn poll(&mut self) -> std::io::Result<Vec<LedgerEntry>> {
+        let mut seen = HashMap::new();
+        let mut ready = Vec::new();
+
+        for entry in fs::read_dir(&self.inbox)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !metadata.is_file() || hidden {
+                continue;
+            }
+
+            let path = entry.path();
+            let state = (metadata.len(), metadata.modified()?);
+            if self.pending.get(&path) == Some(&state) {
+                ready.push(path);
+            } else {
+                seen.insert(path, state);
+            }
+        }
+        self.pending = seen;
+        ready.sort();
+
+        let mut handled = Vec::new();
+        for path in ready {
+            let entry = self.handle(&path)?;
+            self.append_to_ledger(&entry)?;
+            handled.push(entry);
+        }
+
+        Ok(handled)
+    }
+
+    fn handle(&self, path: &Path) -> std::io::Result<LedgerEntry> {
+        let file = path
+            .file_name()
+            .expect("inbox entries have a name")
+            .to_string_lossy()
+            .to_string();
+        let mut entry = LedgerEntry {
+            file: file.clone(),
+            sha256: file_sha256(path)?,
+            status: "failed".to_string(),
+            moved_to: String::new(),
+            rows: 0,
+            rejected: 0,
+            message: String::new(),
+            handled_at: Utc::now().naive_utc(),
+        };
+
+        let processed = |e: &LedgerEntry| {
+            e.status == "processed" && e.file == entry.file && e.sha256 == entry.sha256
+        };
+        if self.ledger.iter().any(processed) {
+            entry.status = "skipped".to_string();
+            entry.message = "already processed".to_string();
+        } else {
+            let mut pipeline = Pipeline::new(CsvSource::new(path))
+                .transform(Clamp::default())
+                .sink(CsvLoader::new(&self.sink).append(true))
+                .error_policy(ErrorPolicy::Reject)
+                .lineage(true);
+            if let Some(kv) = &self.kv {
+                pipeline = pipeline.sink(kv.clone());
+            }
+            let result = pipeline.run();
+            match result {
+                Ok(report) => {
+                    entry.status = "processed".to_string();
+                    entry.rows = report.cleaned.len();
+                    entry.rejected = report.rejects.len();
+                    entry.message = format!("total {}", report.summary.total);
+                }
+                Err(err) => entry.message = err.to_string(),
+            }
+        }
+
+        let target = free_path(&self.inbox.join(&entry.status), &file);
+        fs::rename(path, &target)?;
+        entry.moved_to = target
+            .strip_prefix(&self.inbox)
+            .unwrap_or(&target)
+            .display()
+            .to_string();
+        Ok(entry)
+    }
+
+    fn append_to_ledger(&mut self, entry: &LedgerEntry) -> std::io::Result<()> {
+        let path = self.inbox.join(LEDGER);
+        let is_new = !path.exists();
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(is_new)
+            .delimiter(b';')
+            .from_writer(file);
+        wtr.serialize(entry)?;
+        wtr.flush()?;
+
+        self.ledger.push(entry.clone());
+        Ok(())
+    }
+}
+
+// `dir/file`, or `dir/stem.N.ext` with the first N not taken
+fn free_path(dir: &Path, file: &str) -> PathBuf {
+    let path = dir.join(file);
+    if !path.exists() {
+        return path;
+    }
+
+    let (stem, ext) = match file.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (file, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{}.{}{}", stem, n, ext)))
+        .find(|path| !path.exists())
+        .expect("some numbered name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_processed_once_stable_and_moved() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let inbox = dir.path().join("inbox");
+        let sink = dir.path().join("cleaned.csv");
+        let mut watcher = Watcher::new(&inbox, &sink).expect("Error creating watcher");
+        fs::write(inbox.join("a.csv"), "id;value\n1;-5\n2;50\n").unwrap();
+        fs::write(inbox.join("b.csv"), "id;value\n3;abc\n").unwrap();
+
+        // first sight of the files, wait for them to stop changing
+        assert!(watcher.poll().expect("Error polling").is_empty());
+        let handled = watcher.poll().expect("Error polling");
+
+        assert_eq!(handled.len(), 2);
+        assert_eq!(handled[0].status, "processed");
+        assert_eq!(handled[0].rows, 2);
+        assert_eq!(handled[1].status, "failed");
+        assert!(inbox.join("processed/a.csv").exists());
+        assert!(inbox.join("failed/b.csv").exists());
+        assert!(!inbox.join("a.csv").exists());
+        let written = fs::read_to_string(&sink).expect("Error reading sink");
+        assert_eq!(written, "id;value\n1;0\n2;50\n");
+        assert!(dir.path().join("cleaned.csv.lineage.json").exists());
+    }
+
+    #[test]
+    fn processed_files_are_upserted_into_the_store() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let inbox = dir.path().join("inbox");
+        let store = KvLoader::open(dir.path().join("store")).expect("Error opening store");
+        let mut watcher = Watcher::new(&inbox, dir.path().join("cleaned.csv"))
+            .expect("Error creating watcher")
+            .kv(store.clone());
+
+        drop_and_handle(&mut watcher, &inbox, "id;value\n1;10\n2;20\n");
+        drop_and_handle(&mut watcher, &inbox, "id;value\n1;15\n");
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            store.get(1).expect("Error reading store").unwrap().value,
+            15
+        );
+    }
+
+    // drop a file in the inbox and poll until it is handled
+    fn drop_and_handle(watcher: &mut Watcher, inbox: &Path, content: &str) -> LedgerEntry {
+        fs::write(inbox.join("a.csv"), content).unwrap();
+        watcher.poll().expect("Error polling");
+        let mut handled = watcher.poll().expect("Error polling");
+        assert_eq!(handled.len(), 1);
+        handled.remove(0)
+    }
+
+    #[test]
+    fn ledger_survives_restart_and_skips_processed_content() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let inbox = dir.path().join("inbox");
+        let sink = dir.path().join("cleaned.csv");
+
+        let mut watcher = Watcher::new(&inbox, &sink).expect("Error creating watcher");
+        drop_and_handle(&mut watcher, &inbox, "id;value\n1;10\n");
+
+        let mut watcher = Watcher::new(&inbox, &sink).expect("Error creating watcher");
+        assert_eq!(watcher.ledger().len(), 1);
+        let entry = drop_and_handle(&mut watcher, &inbox, "id;value\n1;10\n");
+
+        assert_eq!(entry.status, "skipped");
+        assert_eq!(entry.message, "already processed");
+        assert_eq!(
+            entry.moved_to,
+            Path::new("skipped").join("a.csv").display().to_string()
+        );
+        assert!(!inbox.join("failed/a.csv").exists());
+        assert_eq!(watcher.ledger().len(), 2);
+        let written = fs::read_to_string(&sink).expect("Error reading sink");
+        assert_eq!(written, "id;value\n1;10\n");
+    }
+
+    #[test]
+    fn reused_name_with_new_content_is_processed_without_overwriting() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let inbox = dir.path().join("inbox");
+        let sink = dir.path().join("cleaned.csv");
+        let mut watcher = Watcher::new(&inbox, &sink).expect("Error creating watcher");
+
+        drop_and_handle(&mut watcher, &inbox, "id;value\n1;10\n");
+        let entry = drop_and_handle(&mut watcher, &inbox, "id;value\n2;20\n");
+
+        assert_eq!(entry.status, "processed");
+        assert_eq!(
+            fs::read_to_string(inbox.join("processed/a.csv")).unwrap(),
+            "id;value\n1;10\n"
+        );
+        assert_eq!(
+            fs::read_to_string(inbox.join("processed/a.1.csv")).unwrap(),
+            "id;value\n2;20\n"
+        );
+        let written = fs::read_to_string(&sink).expect("Error reading sink");
+        assert_eq!(written, "id;value\n1;10\n2;20\n");
+    }
+
+    #[test]
+    fn failed_file_is_retried_once_fixed() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let inbox = dir.path().join("inbox");
+        let sink = dir.path().join("cleaned.csv");
+        let mut watcher = Watcher::new(&inbox, &sink).expect("Error creating watcher");
+
+        let first = drop_and_handle(&mut watcher, &inbox, "id;value\n1;abc\n");
+        let second = drop_and_handle(&mut watcher, &inbox, "id;value\n1;abc\n");
+        let fixed = drop_and_handle(&mut watcher, &inbox, "id;value\n1;10\n");
+
+        assert_eq!(first.status, "failed");
+        assert_eq!(second.status, "failed");
+        assert_ne!(second.message, "already processed");
+        assert!(inbox.join("failed/a.csv").exists());
+        assert!(inbox.join("failed/a.1.csv").exists());
+        assert_eq!(fixed.status, "processed");
+        assert!(inbox.join("processed/a.csv").exists());
+    }
+
+    #[test]
+    fn free_path_numbers_taken_names() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        fs::write(dir.path().join("daily.csv"), "").unwrap();
+        fs::write(dir.path().join("daily.1.csv"), "").unwrap();
+        fs::write(dir.path().join("README"), "").unwrap();
+
+        assert_eq!(free_path(dir.path(), "new.csv"), dir.path().join("new.csv"));
+        assert_eq!(
+            free_path(dir.path(), "daily.csv"),
+            dir.path().join("daily.2.csv")
+        );
+        assert_eq!(free_path(dir.path(), "README"), dir.path().join("README.1"));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use serde::Serialize;
+
+use crate::load::{CsvLoader, Loader, Table};
+
+// A cleaned record with the time the event happened (UTC)
+#[derive(Debug, Clone)]
+pub struct TimedData {
+    pub id: u32,
+    pub value: i32,
+    pub timestamp: NaiveDateTime,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidWindow(pub String);
+
+impl fmt::Display for InvalidWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidWindow {}
+
+// Windows are aligned on the unix epoch, so a 1 hour window always starts on the hour.
+// A tumbling window is a sliding window whose slide equals its size.
+#[derive(Debug, Clone, Copy)]
+pub struct Window {
+    size: i64,
+    slide: i64,
+}
+
+impl Window {
+    pub fn tumbling(size: TimeDelta) -> Result<Self, InvalidWindow> {
+        Self::sliding(size, size)
+    }
+
+    // Size and slide are whole numbers of seconds, at least one
+    pub fn sliding(size: TimeDelta, slide: TimeDelta) -> Result<Self, InvalidWindow> {
+        Ok(Window {
+            size: whole_seconds(size, "size")?,
+            slide: whole_seconds(slide, "slide")?,
+        })
+    }
+
+    // Start (in seconds) of every window containing `ts`
+    fn starts(&self, ts: i64) -> impl Iterator<Item = i64> {
+        let last = ts.div_euclid(self.slide) * self.slide;
+        let size = self.size;
+        let slide = self.slide;
+
+        (0..)
+            .map(move |n| last - n * slide)
+            .take_while(move |start| ts - start < size)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct WindowSummary {
+    pub window_start: NaiveDateTime,
+    pub window_end: NaiveDateTime,
+    pub count: usize,
+    pub sum: i64,
+    pub average: f64,
+    pub min: i32,
+    pub max: i32,
+}
+
+// Group records into windows by timestamp and summarize each non empty window,
+// ordered by window start
+pub fn aggregate_windows(records: &[TimedData], window: Window) -> Vec<WindowSummary> {
+    let mut windows: BTreeMap<i64, (usize, i64, i32, i32)> = BTreeMap::new();
+
+    for record in records {
+        for start in window.starts(record.timestamp.and_utc().timestamp()) {
+            let acc = windows
+                .entry(start)
+                .or_insert((0, 0, record.value, record.value));
+            acc.0 += 1;
+            acc.1 += record.value as i64;
+            acc.2 = acc.2.min(record.value);
+            acc.3 = acc.3.max(record.value);
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(start, (count, sum, min, max))| WindowSummary {
+            window_start: to_datetime(start),
+            window_end: to_datetime(start + window.size),
+            count,
+            sum,
+            average: sum as f64 / count as f64,
+            min,
+            max,
+        })
+        .collect()
+}
+
+fn whole_seconds(delta: TimeDelta, name: &str) -> Result<i64, InvalidWindow> {
+    if delta.num_seconds() < 1 || delta.subsec_nanos() != 0 {
+        return Err(InvalidWindow(format!(
+            "window {} must be a whole number of seconds, at least one, got {}",
+            name, delta
+        )));
+    }
+    Ok(delta.num_seconds())
+}
+
+fn to_datetime(secs: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(secs, 0)
+        .expect("window bound out of range")
+        .naive_utc()
+}
+
+// Window summary table, one row per window, ready for any loader
+pub fn windowed_table(summaries: &[WindowSummary]) -> Table {
+    let headers = [
+        "window_start",
+        "window_end",
+        "count",
+        "sum",
+        "average",
+        "min",
+        "max",
+    ];
+    let time = |t: &NaiveDateTime| t.format("%Y-%m-%dT%H:%M:%S").to_string();
+
+    Table {
+        headers: headers.iter().map(|h| h.to_string()).collect(),
+        rows: summaries
+            .iter()
+            .map(|s| {
+                vec![
+                    time(&s.window_start),
+                    time(&s.window_end),
+                    s.count.to_string(),
+                    s.sum.to_string(),
+                    s.average.to_string(),
+                    s.min.to_string(),
+                    s.max.to_string(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+pub fn write_windows_to_csv(summaries: &[WindowSummary], filename: &str) -> std::io::Result<()> {
+    CsvLoader::new(filename).load(&windowed_table(summaries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str, id: u32, value: i32) -> TimedData {
+        TimedData {
+            id,
+            value,
+            timestamp: NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap(),
+        }
+    }
+
+    #[test]
+    fn tumbling_windows_split_records_by_period() {
+        let records = vec![
+            at("2024-01-01 10:05:00", 1, 10),
+            at("2024-01-01 10:55:00", 2, 30),
+            at("2024-01-01 11:10:00", 3, 50),
+        ];
+
+        let window = Window::tumbling(TimeDelta::hours(1)).expect("Error creating window");
+        let summaries = aggregate_windows(&records, window);
+
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].window_start.to_string(), "2024-01-01 10:00:00");
+        assert_eq!(summaries[0].window_end.to_string(), "2024-01-01 11:00:00");
+        assert_eq!(summaries[0].count, 2);
+        assert_eq!(summaries[0].sum, 40);
+        assert_eq!(summaries[0].average, 20.0);
+        assert_eq!(summaries[0].min, 10);
+        assert_eq!(summaries[0].max, 30);
+        assert_eq!(summaries[1].count, 1);
+        assert_eq!(summaries[1].sum, 50);
+    }
+
+    #[test]
+    fn sliding_windows_overlap() {
+        let records = vec![
+            at("2024-01-01 10:20:00", 1, 10),
+            at("2024-01-01 10:40:00", 2, 20),
+        ];
+
+        let window = Window::sliding(TimeDelta::minutes(30), TimeDelta::minutes(15))
+            .expect("Error creating window");
+        let summaries = aggregate_windows(&records, window);
+
+        let counts: Vec<_> = summaries
+            .iter()
+            .map(|s| (s.window_start.format("%H:%M").to_string(), s.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("10:00".to_string(), 1),
+                ("10:15".to_string(), 2),
+                ("10:30".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_records_no_windows() {
+        let window = Window::tumbling(TimeDelta::minutes(5)).expect("Error creating window");
+        let summaries = aggregate_windows(&[], window);
+
+        assert!(summaries.is_empty());
+    }
+
+    #[test]
+    fn windows_shorter_than_a_second_are_refused() {
+        assert!(Window::tumbling(TimeDelta::milliseconds(500)).is_err());
+        assert!(Window::tumbling(TimeDelta::milliseconds(1500)).is_err());
+        assert!(Window::sliding(TimeDelta::minutes(5), TimeDelta::zero()).is_err());
+        assert!(Window::sliding(TimeDelta::minutes(5), TimeDelta::minutes(-1)).is_err());
+    }
+
+    #[test]
+    fn windowed_table_has_one_row_per_window() {
+        let records = vec![
+            at("2024-01-01 10:05:00", 1, 10),
+            at("2024-01-01 10:55:00", 2, 30),
+            at("2024-01-01 11:10:00", 3, 50),
+        ];
+        let window = Window::tumbling(TimeDelta::hours(1)).expect("Error creating window");
+
+        let table = windowed_table(&aggregate_windows(&records, window));
+
+        assert_eq!(
+            table.headers,
+            vec![
+                "window_start",
+                "window_end",
+                "count",
+                "sum",
+                "average",
+                "min",
+                "max"
+            ]
+        );
+        assert_eq!(
+            table.rows,
+            vec![
+                vec![
+                    "2024-01-01T10:00:00",
+                    "2024-01-01T11:00:00",
+                    "2",
+                    "40",
+                    "20",
+                    "10",
+                    "30"
+                ],
+                vec![
+                    "2024-01-01T11:00:00",
+                    "2024-01-01T12:00:00",
+                    "1",
+                    "50",
+                    "50",
+                    "50",
+                    "50"
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_are_written_through_a_loader() {
+        let dir = tempfile::tempdir().expect("Error creating temp dir");
+        let path = dir.path().join("windows.csv");
+        let window = Window::tumbling(TimeDelta::hours(1)).expect("Error creating window");
+        let summaries = aggregate_windows(&[at("2024-01-01 10:05:00", 1, 10)], window);
+
+        write_windows_to_csv(&summaries, path.to_str().unwrap()).expect("Error writing windows");
+
+        let content = std::fs::read_to_string(&path).expect("Error reading file");
+        assert_eq!(
+            content,
+            "window_start;window_end;count;sum;average;min;max\n\
+             2024-01-01T10:00:00;2024-01-01T11:00:00;1;10;10;10;10\n"
+        );
+    }
+}
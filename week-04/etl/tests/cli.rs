@@ -0,0 +1,44 @@
+// End to end runs of the etl binary, each in its own temp dir as it writes to the
+// working directory
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn etl(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_etl"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Error running etl")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "etl failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).expect("Error reading output")
+}
+
+#[test]
+fn get_reads_the_current_state_kept_by_repeated_runs() {
+    let dir = tempfile::tempdir().expect("Error creating temp dir");
+
+    stdout(&etl(dir.path(), &["--kv", "store"]));
+    assert_eq!(
+        stdout(&etl(dir.path(), &["get", "store", "6"])).trim(),
+        r#"{"id":6,"value":100}"#
+    );
+
+    stdout(&etl(
+        dir.path(),
+        &["--kv", "store", "--rule", "value = value / 2"],
+    ));
+    assert_eq!(
+        stdout(&etl(dir.path(), &["get", "store", "6"])).trim(),
+        r#"{"id":6,"value":50}"#
+    );
+
+    let missing = etl(dir.path(), &["get", "store", "99"]);
+    assert!(!missing.status.success());
+}
@@ -0,0 +1,84 @@
+// Golden file tests: every directory in tests/fixtures holds an `input.csv` of raw data,
+// a `config.txt` listing the transforms to run, one per line, and the `expected.csv` the
+// pipeline must write for it. Records rejected by a transform are compared with
+// `rejects.csv`, which is absent when nothing is rejected. The config is read with
+// read_transforms, like the --transforms file of the etl binary:
+//
+//     clamp 10 50
+//     rule value = if value < 0 then null else value
+//
+// Run with ETL_UPDATE_GOLDEN=1 to regenerate the expected files after an intended change.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use etl::{read_transforms, write_rejects_to_csv, CsvLoader, CsvSource, ErrorPolicy, Pipeline};
+
+fn fixtures() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut cases: Vec<_> = fs::read_dir(root)
+        .expect("Error reading fixtures")
+        .map(|entry| entry.expect("Error reading fixture").path())
+        .filter(|path| path.is_dir())
+        .collect();
+    cases.sort();
+    cases
+}
+
+// Pipeline reading the input of a fixture with the transforms of its config
+fn pipeline(case: &Path) -> Pipeline {
+    let transforms = read_transforms(case.join("config.txt")).expect("Error reading config");
+    Pipeline::new(CsvSource::new(case.join("input.csv")))
+        .transforms(transforms)
+        .error_policy(ErrorPolicy::Reject)
+}
+
+fn read(path: &Path) -> Option<String> {
+    path.exists()
+        .then(|| fs::read_to_string(path).expect("Error reading file"))
+}
+
+#[test]
+fn cleaned_output_matches_golden_files() {
+    let update = std::env::var_os("ETL_UPDATE_GOLDEN").is_some();
+    let out = tempfile::tempdir().expect("Error creating temp dir");
+    let cases = fixtures();
+    assert!(!cases.is_empty(), "no fixtures found");
+
+    for case in cases {
+        let name = case.file_name().unwrap().to_string_lossy().to_string();
+        let output = out.path().join(format!("{name}.csv"));
+        let rejects = out.path().join(format!("{name}.rejects.csv"));
+
+        let report = pipeline(&case)
+            .sink(CsvLoader::new(&output))
+            .run()
+            .expect("Error running pipeline");
+        if !report.rejects.is_empty() {
+            write_rejects_to_csv(&report.rejects, rejects.to_str().unwrap())
+                .expect("Error writing rejects");
+        }
+
+        let actual = read(&output).expect("Error reading output");
+        let actual_rejects = read(&rejects);
+        if update {
+            fs::write(case.join("expected.csv"), &actual).expect("Error updating golden file");
+            match &actual_rejects {
+                Some(r) => fs::write(case.join("rejects.csv"), r),
+                None if case.join("rejects.csv").exists() => {
+                    fs::remove_file(case.join("rejects.csv"))
+                }
+                None => Ok(()),
+            }
+            .expect("Error updating golden rejects");
+            continue;
+        }
+
+        let expected = read(&case.join("expected.csv")).expect("Error reading golden");
+        assert_eq!(actual, expected, "golden mismatch for fixture {name}");
+        assert_eq!(
+            actual_rejects,
+            read(&case.join("rejects.csv")),
+            "golden rejects mismatch for fixture {name}"
+        );
+    }
+}